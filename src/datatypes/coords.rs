@@ -4,6 +4,7 @@ use std::hash::Hash;
 use std::ops::{Add, RangeInclusive};
 
 use itertools::{Itertools, Product};
+use rand::Rng;
 
 #[cfg(test)]
 pub mod coords_tests;
@@ -14,6 +15,30 @@ pub enum Dimensionality {
     TwoDimensional,
     ThreeDimensional,
 }
+impl Dimensionality {
+    /// The highest individual dimension available
+    pub fn last_dimension(&self) -> Dimension {
+        match self {
+            Dimensionality::OneDimensional => Dimension::X,
+            Dimensionality::TwoDimensional => Dimension::Y,
+            Dimensionality::ThreeDimensional => Dimension::Z,
+        }
+    }
+
+    /// Each individual dimension available, in order
+    pub fn dimensions(&self) -> &'static [Dimension] {
+        match self {
+            Dimensionality::OneDimensional => &[Dimension::X],
+            Dimensionality::TwoDimensional => &[Dimension::X, Dimension::Y],
+            Dimensionality::ThreeDimensional => &[Dimension::X, Dimension::Y, Dimension::Z],
+        }
+    }
+
+    /// Whether `dimension` can be addressed. `Dimension::All` is always valid.
+    pub fn has(&self, dimension: Dimension) -> bool {
+        dimension == Dimension::All || dimension <= self.last_dimension()
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Dimension {
@@ -121,6 +146,14 @@ pub trait Coordinate:
     fn set_y(&mut self, y: isize);
     fn set_z(&mut self, z: isize);
     fn set_all(&mut self, value: isize);
+    fn get(&self, dimension: Dimension) -> isize {
+        match dimension {
+            Dimension::X => self.x(),
+            Dimension::Y => self.y(),
+            Dimension::Z => self.z(),
+            Dimension::All => panic!("Cannot get the value of all dimensions at once."),
+        }
+    }
     fn set(&mut self, dimension: Dimension, value: isize) {
         match dimension {
             Dimension::X => self.set_x(value),
@@ -132,6 +165,15 @@ pub trait Coordinate:
     fn offset(self, dimension: Dimension, value: isize) -> OffsetIterator<Self> {
         OffsetIterator::new(self, dimension, value)
     }
+    /// Which half of a checkerboard the coordinate lies on, 0 or 1;
+    /// neighbors one step along an axis lie on different halves
+    fn parity(&self) -> usize {
+        Self::dimensionality()
+            .dimensions()
+            .iter()
+            .map(|d| self.get(*d).rem_euclid(2) as usize)
+            .fold(0, |parity, axis| parity ^ axis)
+    }
     fn dimensionality() -> Dimensionality;
 }
 
@@ -263,11 +305,7 @@ impl<C: Coordinate> OffsetIterator<C> {
             // dimensions
             // TODO hacky, could be implemented in a cleaner way. Not a high priority
             // though.
-            last_dim: match C::dimensionality() {
-                Dimensionality::OneDimensional => Dimension::X,
-                Dimensionality::TwoDimensional => Dimension::Y,
-                Dimensionality::ThreeDimensional => Dimension::Z,
-            },
+            last_dim: C::dimensionality().last_dimension(),
         }
     }
 }
@@ -280,7 +318,7 @@ impl<C: Coordinate> Iterator for OffsetIterator<C> {
         if dim > self.last_dim {
             return None;
         }
-        let mut c = self.coord.clone();
+        let mut c = self.coord;
         c.set(dim, self.value);
         Some(c)
     }
@@ -302,14 +340,73 @@ pub trait ClosedSet<C: Coordinate, I: Iterator<Item = C>>:
 }
 
 // TODO hack to let FixedGrid accept a ClosedSet
-pub trait CoordinateBounds<C: Coordinate>: IntoIterator<Item = C> {}
+pub trait CoordinateBounds<C: Coordinate>: IntoIterator<Item = C> + Clone {
+    fn in_bounds(&self, coord: C) -> bool;
+    /// Number of coordinates iterating over these bounds would yield
+    fn cell_count(&self) -> u128;
+}
 
-impl CoordinateBounds<Coordinate1D> for BoundingBox1D {}
-impl CoordinateBounds<Coordinate2D> for BoundingBox2D {}
-impl CoordinateBounds<Coordinate3D> for BoundingBox3D {}
-impl CoordinateBounds<Coordinate1D> for Vec<Coordinate1D> {}
-impl CoordinateBounds<Coordinate2D> for Vec<Coordinate2D> {}
-impl CoordinateBounds<Coordinate3D> for Vec<Coordinate3D> {}
+/// Number of integers in `low..=high`
+fn breadth((low, high): (isize, isize)) -> u128 {
+    (high as i128 - low as i128 + 1).max(0) as u128
+}
+
+impl CoordinateBounds<Coordinate1D> for BoundingBox1D {
+    fn in_bounds(&self, coord: Coordinate1D) -> bool {
+        !self.outside(coord)
+    }
+    fn cell_count(&self) -> u128 {
+        breadth((self.low, self.high))
+    }
+}
+impl CoordinateBounds<Coordinate2D> for BoundingBox2D {
+    fn in_bounds(&self, coord: Coordinate2D) -> bool {
+        !self.outside(coord)
+    }
+    fn cell_count(&self) -> u128 {
+        breadth(self.x) * breadth(self.y)
+    }
+}
+impl CoordinateBounds<Coordinate3D> for BoundingBox3D {
+    fn in_bounds(&self, coord: Coordinate3D) -> bool {
+        !self.outside(coord)
+    }
+    fn cell_count(&self) -> u128 {
+        breadth(self.x) * breadth(self.y) * breadth(self.z)
+    }
+}
+impl CoordinateBounds<Coordinate2D> for Circle2D {
+    fn in_bounds(&self, coord: Coordinate2D) -> bool {
+        !self.outside(coord)
+    }
+    fn cell_count(&self) -> u128 {
+        self.into_iter().count() as u128
+    }
+}
+impl CoordinateBounds<Coordinate1D> for Vec<Coordinate1D> {
+    fn in_bounds(&self, coord: Coordinate1D) -> bool {
+        self.contains(&coord)
+    }
+    fn cell_count(&self) -> u128 {
+        self.len() as u128
+    }
+}
+impl CoordinateBounds<Coordinate2D> for Vec<Coordinate2D> {
+    fn in_bounds(&self, coord: Coordinate2D) -> bool {
+        self.contains(&coord)
+    }
+    fn cell_count(&self) -> u128 {
+        self.len() as u128
+    }
+}
+impl CoordinateBounds<Coordinate3D> for Vec<Coordinate3D> {
+    fn in_bounds(&self, coord: Coordinate3D) -> bool {
+        self.contains(&coord)
+    }
+    fn cell_count(&self) -> u128 {
+        self.len() as u128
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Contains {
@@ -318,7 +415,7 @@ pub enum Contains {
     OnEdge,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct BoundingBox1D {
     low: isize,
     high: isize,
@@ -327,6 +424,18 @@ impl BoundingBox1D {
     pub fn new(low: isize, high: isize) -> Self {
         Self { low, high }
     }
+    pub fn low(&self) -> isize {
+        self.low
+    }
+    pub fn high(&self) -> isize {
+        self.high
+    }
+
+    /// A uniformly random coordinate within the box. Panics if the box is
+    /// empty.
+    pub fn random_coord<R: Rng + ?Sized>(&self, rng: &mut R) -> Coordinate1D {
+        Coordinate1D::new(rng.gen_range(self.low..=self.high))
+    }
 }
 impl ClosedSet<Coordinate1D, BoundingBox1DIterator> for BoundingBox1D {
     fn contains(&self, coord: Coordinate1D) -> Contains {
@@ -346,7 +455,7 @@ impl IntoIterator for BoundingBox1D {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct BoundingBox2D {
     x: (isize, isize),
     y: (isize, isize),
@@ -356,6 +465,21 @@ impl BoundingBox2D {
     pub fn new(x: (isize, isize), y: (isize, isize)) -> Self {
         Self { x, y }
     }
+    pub fn x(&self) -> (isize, isize) {
+        self.x
+    }
+    pub fn y(&self) -> (isize, isize) {
+        self.y
+    }
+
+    /// A uniformly random coordinate within the box. Panics if the box is
+    /// empty.
+    pub fn random_coord<R: Rng + ?Sized>(&self, rng: &mut R) -> Coordinate2D {
+        Coordinate2D::new(
+            rng.gen_range(self.x.0..=self.x.1),
+            rng.gen_range(self.y.0..=self.y.1),
+        )
+    }
 }
 
 impl ClosedSet<Coordinate2D, BoundingBox2DIterator> for BoundingBox2D {
@@ -388,7 +512,7 @@ impl IntoIterator for BoundingBox2D {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct BoundingBox3D {
     x: (isize, isize),
     y: (isize, isize),
@@ -399,6 +523,25 @@ impl BoundingBox3D {
     pub fn new(x: (isize, isize), y: (isize, isize), z: (isize, isize)) -> Self {
         Self { x, y, z }
     }
+    pub fn x(&self) -> (isize, isize) {
+        self.x
+    }
+    pub fn y(&self) -> (isize, isize) {
+        self.y
+    }
+    pub fn z(&self) -> (isize, isize) {
+        self.z
+    }
+
+    /// A uniformly random coordinate within the box. Panics if the box is
+    /// empty.
+    pub fn random_coord<R: Rng + ?Sized>(&self, rng: &mut R) -> Coordinate3D {
+        Coordinate3D::new(
+            rng.gen_range(self.x.0..=self.x.1),
+            rng.gen_range(self.y.0..=self.y.1),
+            rng.gen_range(self.z.0..=self.z.1),
+        )
+    }
 }
 
 impl ClosedSet<Coordinate3D, BoundingBox3DIterator> for BoundingBox3D {
@@ -498,8 +641,8 @@ impl Iterator for BoundingBox3DIterator {
     }
 }
 
-// TODO finish
-#[derive(Debug, Clone, Copy)]
+/// Every lattice point within Euclidean distance `radius` of `center`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Circle2D {
     center: Coordinate2D,
     radius: isize,
@@ -509,13 +652,26 @@ impl Circle2D {
     pub fn new(center: Coordinate2D, radius: isize) -> Self {
         Self { center, radius }
     }
+    pub fn center(&self) -> Coordinate2D {
+        self.center
+    }
+    pub fn radius(&self) -> isize {
+        self.radius
+    }
 }
 
 impl ClosedSet<Coordinate2D, Circle2DIterator> for Circle2D {
     fn contains(&self, coord: Coordinate2D) -> Contains {
-        let (coord_x, coord_y) = (coord.x(), coord.y());
-        let (center_x, center_y) = (self.center.x(), self.center.y());
-        match (coord_x * center_x + coord_y * center_y).cmp(&self.radius) {
+        // Squares of differences between isizes only fit in a u128, and
+        // their sum may not; a sum that overflows is well past the edge
+        let dx = (coord.x() as i128 - self.center.x() as i128).unsigned_abs();
+        let dy = (coord.y() as i128 - self.center.y() as i128).unsigned_abs();
+        let radius = (self.radius as i128).unsigned_abs();
+        let distance = match (dx * dx).checked_add(dy * dy) {
+            Some(distance) => distance,
+            None => return Contains::Outside,
+        };
+        match distance.cmp(&(radius * radius)) {
             Ordering::Equal => Contains::OnEdge,
             Ordering::Less => Contains::Within,
             Ordering::Greater => Contains::Outside,
@@ -532,16 +688,29 @@ impl IntoIterator for Circle2D {
 }
 
 #[derive(Debug, Clone)]
-pub struct Circle2DIterator {}
+pub struct Circle2DIterator {
+    circle: Circle2D,
+    bounding_box: BoundingBox2DIterator,
+}
 impl Circle2DIterator {
     pub fn new(center: Coordinate2D, radius: isize) -> Self {
-        Self {}
+        let (x, y) = (center.x(), center.y());
+        Self {
+            circle: Circle2D::new(center, radius),
+            // Cells past the edges of isize can't be represented, so the
+            // bounding box is clamped to them
+            bounding_box: BoundingBox2DIterator::new(
+                (x.saturating_sub(radius), x.saturating_add(radius)),
+                (y.saturating_sub(radius), y.saturating_add(radius)),
+            ),
+        }
     }
 }
 impl Iterator for Circle2DIterator {
     type Item = Coordinate2D;
 
     fn next(&mut self) -> Option<Self::Item> {
-        todo!()
+        let circle = self.circle;
+        self.bounding_box.find(|c| !circle.outside(*c))
     }
 }
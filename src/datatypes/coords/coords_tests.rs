@@ -368,6 +368,8 @@ pub mod coordinates_tests {
 #[cfg(test)]
 pub mod closed_set_tests {
     use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
 
     /// 1D
 
@@ -386,6 +388,34 @@ pub mod closed_set_tests {
         assert!(bb.outside(Coordinate1D::new(6)));
     }
 
+    #[test]
+    fn bounding_box_1d_cell_count() {
+        assert_eq!(BoundingBox1D::new(1, 5).cell_count(), 5);
+        assert_eq!(BoundingBox1D::new(3, 3).cell_count(), 1);
+        assert_eq!(BoundingBox1D::new(5, 1).cell_count(), 0);
+        assert_eq!(
+            BoundingBox1D::new(isize::MIN, isize::MAX).cell_count(),
+            1 << usize::BITS
+        );
+    }
+
+    #[test]
+    fn bounding_box_1d_random_coord() {
+        let bb = BoundingBox1D::new(-3, 3);
+        let sample = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..100)
+                .map(|_| bb.random_coord(&mut rng))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(sample(7), sample(7));
+        assert!(sample(7).into_iter().all(|c| bb.in_bounds(c)));
+        assert_eq!(
+            BoundingBox1D::new(4, 4).random_coord(&mut StdRng::seed_from_u64(0)),
+            Coordinate1D::new(4)
+        );
+    }
+
     #[test]
     fn bounding_box_1d_on_edge() {
         let bb = BoundingBox1D::new(1, 5);
@@ -416,6 +446,25 @@ pub mod closed_set_tests {
         }
     }
 
+    #[test]
+    fn bounding_box_2d_cell_count() {
+        let bb = BoundingBox2D::new((1, 5), (-1, 1));
+        assert_eq!(bb.cell_count(), 15);
+        assert_eq!(bb.cell_count(), bb.into_iter().count() as u128);
+    }
+
+    #[test]
+    fn bounding_box_2d_random_coord() {
+        let bb = BoundingBox2D::new((1, 5), (-1, 1));
+        let mut rng = StdRng::seed_from_u64(7);
+        let coords = (0..200)
+            .map(|_| bb.random_coord(&mut rng))
+            .collect::<Vec<_>>();
+        assert!(coords.iter().all(|c| bb.in_bounds(*c)));
+        // Every cell of this small box turns up
+        assert_eq!(coords.iter().unique().count(), 15);
+    }
+
     #[test]
     fn bounding_box_2d_on_edge() {
         let bb = BoundingBox2D::new((1, 5), (-1, 1));
@@ -456,6 +505,24 @@ pub mod closed_set_tests {
         }
     }
 
+    #[test]
+    fn bounding_box_3d_cell_count() {
+        let bb = BoundingBox3D::new((1, 5), (-2, 2), (5, 10));
+        assert_eq!(bb.cell_count(), 5 * 5 * 6);
+        assert_eq!(bb.cell_count(), bb.into_iter().count() as u128);
+        let huge = BoundingBox3D::new((0, 9_999), (0, 9_999), (0, 9_999));
+        assert_eq!(huge.cell_count(), 1_000_000_000_000);
+    }
+
+    #[test]
+    fn bounding_box_3d_random_coord() {
+        let bb = BoundingBox3D::new((1, 5), (-2, 2), (5, 10));
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..100 {
+            assert!(bb.in_bounds(bb.random_coord(&mut rng)));
+        }
+    }
+
     #[test]
     fn bounding_box_3d_on_edge() {
         let bb = BoundingBox3D::new((1, 5), (-2, 2), (5, 10));
@@ -472,4 +539,81 @@ pub mod closed_set_tests {
             assert!(bb.on_edge(Coordinate3D::new(x, 2, z)));
         }
     }
+
+    #[test]
+    fn parity_alternates_along_every_axis() {
+        assert_eq!(Coordinate1D::new(-3).parity(), 1);
+        assert_eq!(Coordinate2D::new(-1, 1).parity(), 0);
+        assert_eq!(Coordinate2D::new(isize::MIN, isize::MAX).parity(), 1);
+        assert_eq!(Coordinate3D::new(1, 1, 1).parity(), 1);
+        assert_eq!(Coordinate3D::new(2, -1, 5).parity(), 0);
+    }
+
+    #[test]
+    fn circle_2d_radius_0_is_center() {
+        let circle = Circle2D::new(Coordinate2D::new(3, -1), 0);
+        assert_eq!(
+            circle.into_iter().collect::<Vec<_>>(),
+            vec![Coordinate2D::new(3, -1)]
+        );
+        assert!(circle.on_edge(Coordinate2D::new(3, -1)));
+        assert!(circle.outside(Coordinate2D::new(3, 0)));
+    }
+
+    #[test]
+    fn circle_2d_radius_2() {
+        let circle = Circle2D::new(Coordinate2D::default(), 2);
+        let cells = circle.into_iter().collect::<Vec<_>>();
+        assert_eq!(cells.len(), 13);
+        assert_eq!(circle.cell_count(), 13);
+        assert!(cells.iter().all(|c| circle.in_bounds(*c)));
+        assert!(circle.within(Coordinate2D::new(1, 1)));
+        assert!(circle.on_edge(Coordinate2D::new(0, -2)));
+        assert!(circle.outside(Coordinate2D::new(1, 2)));
+        assert!(!cells.contains(&Coordinate2D::new(2, 2)));
+    }
+
+    #[test]
+    fn circle_2d_off_origin() {
+        let circle = Circle2D::new(Coordinate2D::new(10, -5), 1);
+        let cells = circle.into_iter().collect::<Vec<_>>();
+        assert_eq!(
+            cells,
+            vec![
+                Coordinate2D::new(9, -5),
+                Coordinate2D::new(10, -6),
+                Coordinate2D::new(10, -5),
+                Coordinate2D::new(10, -4),
+                Coordinate2D::new(11, -5),
+            ]
+        );
+        assert!(circle.outside(Coordinate2D::new(0, 0)));
+        assert!(circle.outside(Coordinate2D::new(11, -4)));
+    }
+
+    #[test]
+    fn circle_2d_far_coordinates_do_not_overflow() {
+        let circle = Circle2D::new(Coordinate2D::new(isize::MIN, isize::MIN), 1);
+        assert!(circle.outside(Coordinate2D::new(isize::MAX, isize::MAX)));
+        assert!(circle.outside(Coordinate2D::new(isize::MAX, isize::MIN)));
+        assert!(circle.on_edge(Coordinate2D::new(isize::MIN + 1, isize::MIN)));
+        assert_eq!(
+            circle.into_iter().collect::<Vec<_>>(),
+            vec![
+                Coordinate2D::new(isize::MIN, isize::MIN),
+                Coordinate2D::new(isize::MIN, isize::MIN + 1),
+                Coordinate2D::new(isize::MIN + 1, isize::MIN),
+            ]
+        );
+        assert_eq!(circle.cell_count(), 3);
+
+        let circle = Circle2D::new(Coordinate2D::new(isize::MAX, 0), 1);
+        assert_eq!(circle.into_iter().count(), 4);
+        assert!(circle.into_iter().all(|c| circle.in_bounds(c)));
+
+        let circle = Circle2D::new(Coordinate2D::default(), isize::MAX);
+        assert!(circle.within(Coordinate2D::new(isize::MAX - 1, 0)));
+        assert!(circle.on_edge(Coordinate2D::new(isize::MAX, 0)));
+        assert!(circle.outside(Coordinate2D::new(isize::MAX, 1)));
+    }
 }
@@ -4,10 +4,54 @@ use crate::datatypes::state::State;
 
 pub trait Neighborhood<S>: Debug + Clone {
     fn count(&self, state: S) -> usize;
+    /// Number of neighbors, regardless of state
+    fn total(&self) -> usize;
+    /// Greatest state among the neighbors, if there are any
+    fn max_state(&self) -> Option<S>;
+    /// Least state among the neighbors, if there are any
+    fn min_state(&self) -> Option<S>;
+}
+
+/// A neighborhood that knows how far each neighbor is from the center cell
+pub trait RingNeighborhood<S>: Neighborhood<S> {
+    /// Count neighbors in `state` whose distance is within `inner..=outer`
+    fn count_in_ring(&self, state: S, inner: usize, outer: usize) -> usize;
 }
 
 impl<S: State> Neighborhood<S> for Vec<S> {
     fn count(&self, state: S) -> usize {
         self.iter().filter(|s| **s == state).count()
     }
+    fn total(&self) -> usize {
+        self.len()
+    }
+    fn max_state(&self) -> Option<S> {
+        self.iter().max().copied()
+    }
+    fn min_state(&self) -> Option<S> {
+        self.iter().min().copied()
+    }
+}
+
+/// Neighbors paired with their distance from the center cell
+impl<S: State> Neighborhood<S> for Vec<(usize, S)> {
+    fn count(&self, state: S) -> usize {
+        self.iter().filter(|(_, s)| *s == state).count()
+    }
+    fn total(&self) -> usize {
+        self.len()
+    }
+    fn max_state(&self) -> Option<S> {
+        self.iter().map(|(_, s)| *s).max()
+    }
+    fn min_state(&self) -> Option<S> {
+        self.iter().map(|(_, s)| *s).min()
+    }
+}
+impl<S: State> RingNeighborhood<S> for Vec<(usize, S)> {
+    fn count_in_ring(&self, state: S, inner: usize, outer: usize) -> usize {
+        self.iter()
+            .filter(|(d, s)| *s == state && inner <= *d && *d <= outer)
+            .count()
+    }
 }
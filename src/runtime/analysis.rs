@@ -0,0 +1,444 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::datatypes::coords::{Coordinate, CoordinateBounds};
+use crate::datatypes::ident::Identifer;
+use crate::datatypes::state::State;
+
+/// Translate a configuration so the minimum corner of its non-default cells
+/// lies on the origin.
+///
+/// Cells in the default state are treated as background and dropped. Returns
+/// the translated cells along with the offset of the original minimum
+/// corner, so that adding the offset to a translated cell recovers its
+/// original position.
+pub fn normalize_config<C: Coordinate, S: State>(cells: &HashMap<C, S>) -> (HashMap<C, S>, C) {
+    let live = cells
+        .iter()
+        .filter(|(_, s)| **s != S::default())
+        .collect::<Vec<_>>();
+    let mut offset = C::default();
+    for dimension in C::dimensionality().dimensions() {
+        if let Some(min) = live.iter().map(|(c, _)| c.get(*dimension)).min() {
+            offset.set(*dimension, min);
+        }
+    }
+    let inverse = negate(offset);
+    let normalized = live.into_iter().map(|(c, s)| (*c + inverse, *s)).collect();
+    (normalized, offset)
+}
+
+fn negate<C: Coordinate>(coord: C) -> C {
+    let mut negated = C::default();
+    for dimension in C::dimensionality().dimensions() {
+        negated.set(*dimension, -coord.get(*dimension));
+    }
+    negated
+}
+
+/// Whether two configurations contain the same pattern, regardless of where
+/// each is positioned.
+pub fn configs_equivalent<C: Coordinate, S: State>(a: &HashMap<C, S>, b: &HashMap<C, S>) -> bool {
+    normalize_config(a).0 == normalize_config(b).0
+}
+
+/// Group non-default cells into connected components. Two cells are
+/// adjacent when one lies at an offset in `neighborhood` from the other.
+pub fn components<C: Coordinate, S: State>(
+    cells: &HashMap<C, S>,
+    neighborhood: &[C],
+) -> Vec<HashSet<C>> {
+    let mut unvisited = cells
+        .iter()
+        .filter(|(_, s)| **s != S::default())
+        .map(|(c, _)| *c)
+        .collect::<HashSet<_>>();
+    let mut components = Vec::new();
+    while let Some(start) = unvisited.iter().next().copied() {
+        unvisited.remove(&start);
+        let mut component = HashSet::new();
+        let mut stack = vec![start];
+        while let Some(coord) = stack.pop() {
+            component.insert(coord);
+            for offset in neighborhood {
+                for adjacent in &[coord + *offset, coord + negate(*offset)] {
+                    if unvisited.remove(adjacent) {
+                        stack.push(*adjacent);
+                    }
+                }
+            }
+        }
+        components.push(component);
+    }
+    components
+}
+
+/// A pattern which recurs, translated, after `period` ticks
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spaceship<C: Coordinate, S: State> {
+    /// The pattern as first seen, normalized to the origin
+    pub cells: HashMap<C, S>,
+    pub period: usize,
+    /// Net translation over one period
+    pub displacement: C,
+}
+
+/// Find spaceships by running `tick` and matching each connected component
+/// of the starting configuration against later generations. A component
+/// counts as a spaceship if its shape recurs somewhere other than where it
+/// started. Components which recur in place, or not at all within
+/// `search_ticks` ticks, are left out.
+pub fn detect_spaceships<C: Coordinate, S: State, F: FnMut() -> HashMap<C, S>>(
+    start: &HashMap<C, S>,
+    neighborhood: &[C],
+    search_ticks: usize,
+    mut tick: F,
+) -> Vec<Spaceship<C, S>> {
+    let shapes = |cells: &HashMap<C, S>| {
+        components(cells, neighborhood)
+            .into_iter()
+            .map(|component| {
+                let cells = component.into_iter().map(|c| (c, cells[&c])).collect();
+                normalize_config(&cells)
+            })
+            .collect::<Vec<_>>()
+    };
+    let mut pending = shapes(start);
+    let mut spaceships = Vec::new();
+    for period in 1..=search_ticks {
+        if pending.is_empty() {
+            break;
+        }
+        let current = shapes(&tick());
+        pending.retain(
+            |(shape, offset)| match current.iter().find(|(other, _)| other == shape) {
+                Some((_, moved)) => {
+                    if moved != offset {
+                        spaceships.push(Spaceship {
+                            cells: shape.clone(),
+                            period,
+                            displacement: *moved + negate(*offset),
+                        });
+                    }
+                    false
+                }
+                None => true,
+            },
+        );
+    }
+    spaceships
+}
+
+/// Whether any non-default cell lies outside of `bounds`
+pub fn escaped<C: Coordinate, S: State, B: CoordinateBounds<C>>(
+    cells: &HashMap<C, S>,
+    bounds: &B,
+) -> bool {
+    cells
+        .iter()
+        .any(|(c, s)| *s != S::default() && !bounds.in_bounds(*c))
+}
+
+/// Number of cells whose state differs between two configurations.
+///
+/// A cell missing from one configuration is read as `default`. Without a
+/// default, a cell present in only one configuration always counts as
+/// different.
+pub fn hamming_distance<I: Identifer, S: State>(
+    a: &HashMap<I, S>,
+    b: &HashMap<I, S>,
+    default: Option<S>,
+) -> usize {
+    let differs =
+        |x: Option<&S>, y: Option<&S>| match (x.or(default.as_ref()), y.or(default.as_ref())) {
+            (Some(x), Some(y)) => x != y,
+            _ => true,
+        };
+    let in_a = a.iter().filter(|(i, s)| differs(Some(s), b.get(i))).count();
+    let only_in_b = b
+        .iter()
+        .filter(|(i, s)| !a.contains_key(i) && differs(None, Some(s)))
+        .count();
+    in_a + only_in_b
+}
+
+/// How a cell differs between two generations; see `diff_generations`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellChange {
+    /// Moved out of the default state
+    Activated,
+    /// Moved into the default state
+    Deactivated,
+    /// Moved between two non-default states
+    Changed,
+}
+
+/// Classify every cell whose state differs between `prev` and `next`, for
+/// highlighting change. A cell missing from either generation is read as
+/// the default state.
+pub fn diff_generations<I: Identifer, S: State>(
+    prev: &HashMap<I, S>,
+    next: &HashMap<I, S>,
+) -> HashMap<I, CellChange> {
+    let state = |cells: &HashMap<I, S>, ident: &I| cells.get(ident).copied().unwrap_or_default();
+    prev.keys()
+        .chain(next.keys())
+        .filter_map(|ident| {
+            let (before, after) = (state(prev, ident), state(next, ident));
+            let change = if before == after {
+                return None;
+            } else if before == S::default() {
+                CellChange::Activated
+            } else if after == S::default() {
+                CellChange::Deactivated
+            } else {
+                CellChange::Changed
+            };
+            Some((*ident, change))
+        })
+        .collect()
+}
+
+/// Fraction of cells in each state
+pub fn state_densities<I: Identifer, S: State>(cells: &HashMap<I, S>) -> HashMap<S, f64> {
+    let mut densities = HashMap::new();
+    for state in cells.values() {
+        *densities.entry(*state).or_insert(0.0) += 1.0;
+    }
+    let total = cells.len() as f64;
+    for density in densities.values_mut() {
+        *density /= total;
+    }
+    densities
+}
+
+/// Shannon entropy, in bits, of the distribution of states
+pub fn shannon_entropy<I: Identifer, S: State>(cells: &HashMap<I, S>) -> f64 {
+    state_densities(cells).values().map(|p| -p * p.log2()).sum()
+}
+
+#[cfg(test)]
+pub mod analysis_tests {
+    use super::*;
+    use crate::datatypes::coords::{BoundingBox2D, Coordinate2D};
+
+    #[test]
+    fn normalize_config_moves_pattern_to_origin() {
+        let cells: HashMap<_, _> = vec![
+            (Coordinate2D::new(5, -3), 1usize),
+            (Coordinate2D::new(6, -3), 1),
+            (Coordinate2D::new(6, -2), 2),
+            (Coordinate2D::new(0, 0), 0),
+        ]
+        .into_iter()
+        .collect();
+        let (normalized, offset) = normalize_config(&cells);
+        assert_eq!(offset, Coordinate2D::new(5, -3));
+        assert_eq!(
+            normalized,
+            vec![
+                (Coordinate2D::new(0, 0), 1),
+                (Coordinate2D::new(1, 0), 1),
+                (Coordinate2D::new(1, 1), 2),
+            ]
+            .into_iter()
+            .collect()
+        );
+    }
+
+    #[test]
+    fn normalize_config_offset_recovers_original() {
+        let cells: HashMap<_, _> = vec![
+            (Coordinate2D::new(-4, 7), 1usize),
+            (Coordinate2D::new(-2, 9), 1),
+        ]
+        .into_iter()
+        .collect();
+        let (normalized, offset) = normalize_config(&cells);
+        let recovered: HashMap<_, _> = normalized
+            .into_iter()
+            .map(|(c, s)| (c + offset, s))
+            .collect();
+        assert_eq!(recovered, cells);
+    }
+
+    #[test]
+    fn normalize_config_of_empty_config() {
+        let cells: HashMap<Coordinate2D, usize> = HashMap::new();
+        assert_eq!(
+            normalize_config(&cells),
+            (HashMap::new(), Coordinate2D::default())
+        );
+    }
+
+    fn glider(x: isize, y: isize) -> HashMap<Coordinate2D, usize> {
+        vec![(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]
+            .into_iter()
+            .map(|(dx, dy)| (Coordinate2D::new(x + dx, y + dy), 1))
+            .collect()
+    }
+
+    #[test]
+    fn translated_gliders_are_equivalent() {
+        let mut other = glider(10, -7);
+        other.insert(Coordinate2D::new(0, 0), 0);
+        assert!(configs_equivalent(&glider(0, 0), &other));
+    }
+
+    #[test]
+    fn glider_and_blinker_are_not_equivalent() {
+        let blinker = vec![(0, 0), (1, 0), (2, 0)]
+            .into_iter()
+            .map(|(x, y)| (Coordinate2D::new(x, y), 1))
+            .collect();
+        assert!(!configs_equivalent(&glider(0, 0), &blinker));
+    }
+
+    fn moore() -> Vec<Coordinate2D> {
+        (-1..=1)
+            .flat_map(|x| (-1..=1).map(move |y| Coordinate2D::new(x, y)))
+            .filter(|c| *c != Coordinate2D::default())
+            .collect()
+    }
+
+    #[test]
+    fn components_split_on_gaps() {
+        let mut cells = glider(0, 0);
+        cells.extend(glider(10, 0));
+        cells.insert(Coordinate2D::new(5, 5), 0);
+        let components = components(&cells, &moore());
+        assert_eq!(components.len(), 2);
+        assert!(components.iter().all(|c| c.len() == 5));
+    }
+
+    #[test]
+    fn components_follow_one_sided_offsets() {
+        let cells = vec![
+            (Coordinate2D::new(0, 0), 1usize),
+            (Coordinate2D::new(0, 2), 1),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(components(&cells, &[Coordinate2D::new(0, 2)]).len(), 1);
+        assert_eq!(components(&cells, &[Coordinate2D::new(0, 1)]).len(), 2);
+    }
+
+    #[test]
+    fn state_densities_sum_to_one() {
+        let cells: HashMap<_, _> = vec![
+            (Coordinate2D::new(0, 0), 0usize),
+            (Coordinate2D::new(1, 0), 1),
+            (Coordinate2D::new(2, 0), 1),
+            (Coordinate2D::new(3, 0), 1),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(
+            state_densities(&cells),
+            vec![(0, 0.25), (1, 0.75)].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn shannon_entropy_of_skewed_distribution() {
+        let cells: HashMap<_, _> = (0..4)
+            .map(|x| (Coordinate2D::new(x, 0), if x == 0 { 1usize } else { 0 }))
+            .collect();
+        let expected = -(0.25f64 * 0.25f64.log2() + 0.75 * 0.75f64.log2());
+        assert!((shannon_entropy(&cells) - expected).abs() < 1e-9);
+        assert_eq!(shannon_entropy(&HashMap::<Coordinate2D, usize>::new()), 0.0);
+    }
+
+    #[test]
+    fn escaped_ignores_default_cells() {
+        let bounds = BoundingBox2D::new((0, 1), (0, 1));
+        let mut cells: HashMap<_, _> = vec![(Coordinate2D::new(0, 0), 1usize)]
+            .into_iter()
+            .collect();
+        cells.insert(Coordinate2D::new(5, 5), 0);
+        assert!(!escaped(&cells, &bounds));
+        cells.insert(Coordinate2D::new(2, 0), 1);
+        assert!(escaped(&cells, &bounds));
+    }
+
+    #[test]
+    fn hamming_distance_counts_differing_cells() {
+        let a: HashMap<_, _> = vec![
+            (Coordinate2D::new(0, 0), 1usize),
+            (Coordinate2D::new(1, 0), 1),
+            (Coordinate2D::new(2, 0), 0),
+        ]
+        .into_iter()
+        .collect();
+        let b: HashMap<_, _> = vec![
+            (Coordinate2D::new(0, 0), 1usize),
+            (Coordinate2D::new(1, 0), 0),
+            (Coordinate2D::new(2, 0), 1),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(hamming_distance(&a, &b, None), 2);
+        assert_eq!(hamming_distance(&a, &a, None), 0);
+    }
+
+    #[test]
+    fn hamming_distance_reads_missing_cells_as_default() {
+        let a: HashMap<_, _> = vec![
+            (Coordinate2D::new(0, 0), 1usize),
+            (Coordinate2D::new(1, 0), 0),
+        ]
+        .into_iter()
+        .collect();
+        let b: HashMap<_, _> = vec![
+            (Coordinate2D::new(0, 0), 1usize),
+            (Coordinate2D::new(5, 5), 1),
+        ]
+        .into_iter()
+        .collect();
+        // (1, 0) matches the default; (5, 5) does not
+        assert_eq!(hamming_distance(&a, &b, Some(0)), 1);
+        assert_eq!(hamming_distance(&b, &a, Some(0)), 1);
+        assert_eq!(hamming_distance(&a, &b, None), 2);
+    }
+
+    #[test]
+    fn diff_of_blinker_phases() {
+        let horizontal = vec![(-1, 0), (0, 0), (1, 0)]
+            .into_iter()
+            .map(|(x, y)| (Coordinate2D::new(x, y), 1usize))
+            .collect();
+        let vertical = vec![(0, -1), (0, 0), (0, 1)]
+            .into_iter()
+            .map(|(x, y)| (Coordinate2D::new(x, y), 1usize))
+            .collect();
+        assert_eq!(
+            diff_generations(&horizontal, &vertical),
+            vec![
+                (Coordinate2D::new(-1, 0), CellChange::Deactivated),
+                (Coordinate2D::new(1, 0), CellChange::Deactivated),
+                (Coordinate2D::new(0, -1), CellChange::Activated),
+                (Coordinate2D::new(0, 1), CellChange::Activated),
+            ]
+            .into_iter()
+            .collect()
+        );
+    }
+
+    #[test]
+    fn diff_between_live_states_is_a_change() {
+        let a = vec![
+            (Coordinate2D::new(0, 0), 1usize),
+            (Coordinate2D::new(1, 0), 0),
+        ]
+        .into_iter()
+        .collect();
+        let b = vec![(Coordinate2D::new(0, 0), 2usize)]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            diff_generations(&a, &b),
+            vec![(Coordinate2D::new(0, 0), CellChange::Changed)]
+                .into_iter()
+                .collect()
+        );
+    }
+}
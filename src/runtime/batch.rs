@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::datatypes::ident::Identifer;
+use crate::datatypes::neighborhood::Neighborhood;
+use crate::datatypes::state::State;
+use crate::runtime::environment::Environment;
+use crate::runtime::{Runtime, SynchronousRuntime};
+
+/// Independent runtimes, run side by side for parameter sweeps. With the
+/// `parallel` feature the runtimes are run concurrently using rayon.
+pub struct SimulationBatch<S: State, N: Neighborhood<S>, E, Schedule> {
+    runtimes: Vec<SynchronousRuntime<S, N, E, Schedule>>,
+}
+impl<S: State, N: Neighborhood<S>, E, Schedule> SimulationBatch<S, N, E, Schedule> {
+    pub fn new(runtimes: Vec<SynchronousRuntime<S, N, E, Schedule>>) -> Self {
+        Self { runtimes }
+    }
+
+    pub fn runtimes(&self) -> &[SynchronousRuntime<S, N, E, Schedule>] {
+        &self.runtimes
+    }
+
+    pub fn into_inner(self) -> Vec<SynchronousRuntime<S, N, E, Schedule>> {
+        self.runtimes
+    }
+}
+impl<
+        I: Identifer + MaybeSend,
+        S: State + MaybeSend,
+        N: Neighborhood<S> + MaybeSend,
+        Schedule: IntoIterator<Item = I> + MaybeSend,
+        E: Environment<I, S, N, Schedule> + MaybeSend,
+    > SimulationBatch<S, N, E, Schedule>
+{
+    /// Run every runtime for `ticks` ticks, returning their final
+    /// snapshots in order
+    pub fn run_all(&mut self, ticks: usize) -> Vec<HashMap<I, S>> {
+        #[cfg(feature = "parallel")]
+        let runtimes = self.runtimes.par_iter_mut();
+        #[cfg(not(feature = "parallel"))]
+        let runtimes = self.runtimes.iter_mut();
+        runtimes
+            .map(|runtime| {
+                runtime.run_ticks(ticks);
+                runtime.environment().snapshot()
+            })
+            .collect()
+    }
+}
+
+/// `Send` with the `parallel` feature, so that runtimes can be run on
+/// other threads; otherwise implemented for every type
+#[cfg(feature = "parallel")]
+pub trait MaybeSend: Send {}
+#[cfg(feature = "parallel")]
+impl<T: Send> MaybeSend for T {}
+/// `Send` with the `parallel` feature, so that runtimes can be run on
+/// other threads; otherwise implemented for every type
+#[cfg(not(feature = "parallel"))]
+pub trait MaybeSend {}
+#[cfg(not(feature = "parallel"))]
+impl<T> MaybeSend for T {}
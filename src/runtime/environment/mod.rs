@@ -1,21 +1,91 @@
 pub mod naive;
+pub mod ring;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::datatypes::ident::Identifer;
 use crate::datatypes::neighborhood::Neighborhood;
 use crate::datatypes::state::State;
 
+/// Error returned when a cell is addressed outside of an environment's bounds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfBounds<I>(pub I);
+
+/// Error returned when an environment would hold more cells than allowed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TooManyCells {
+    pub cells: u128,
+    pub limit: u128,
+}
+
+/// Rough memory needs of an environment, from `FixedGrid::capacity_estimate`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapacityEstimate {
+    /// Cells within the bounds
+    pub cells: u128,
+    /// Cells currently held in the state map
+    pub stored: usize,
+    /// Bytes to hold one generation as a flat array of states
+    pub dense_bytes: u128,
+    /// Bytes to hold one generation as a hash map of the stored cells,
+    /// ignoring the map's spare capacity
+    pub sparse_bytes: u128,
+}
+
 pub trait Environment<I: Identifer, S: State, N: Neighborhood<S>, Schedule: IntoIterator<Item = I>>
 {
     fn set_state(&mut self, ident: I, state: S);
+    fn try_set_state(&mut self, ident: I, state: S) -> Result<(), OutOfBounds<I>> {
+        if !self.contains(ident) {
+            return Err(OutOfBounds(ident));
+        }
+        self.set_state(ident, state);
+        Ok(())
+    }
+    fn contains(&self, ident: I) -> bool {
+        self.get_state(ident).is_some()
+    }
     fn get_state(&self, ident: I) -> Option<S>;
+    /// Number of ticks the cell has spent in its current state. By default
+    /// ages aren't tracked, and every cell reports zero.
+    fn get_age(&self, ident: I) -> Option<usize> {
+        self.get_state(ident).map(|_| 0)
+    }
     fn get_neighborhood(&self, ident: I) -> Option<N>;
 
     fn schedule(&mut self, ident: I);
     fn deschedule(&mut self, ident: I);
     fn get_schedule(&self) -> Schedule; // TODO iterator
+    /// Hold cells in their current state. Frozen cells are left out of the
+    /// schedule, but still appear in their neighbors' neighborhoods.
+    fn freeze(&mut self, idents: HashSet<I>) {
+        for ident in idents {
+            self.deschedule(ident);
+        }
+    }
+
+    /// Overwrite cells in the current tick, outside of the normal rules.
+    /// Unlike `set_state`, the changes are visible immediately. Cells
+    /// outside the environment are ignored.
+    ///
+    /// By default the changes are applied as a tick of their own, so
+    /// environments that track ages should override this.
+    fn perturb(&mut self, changes: HashMap<I, S>) {
+        for (ident, state) in changes {
+            if self.contains(ident) {
+                self.set_state(ident, state);
+            }
+        }
+        self.tick();
+    }
 
     fn snapshot(&self) -> HashMap<I, S>;
     fn tick(&mut self);
+
+    /// Number of cells in the environment, frozen or not. The default
+    /// takes a snapshot to count them, so environments that know their
+    /// size up front should override this.
+    fn cell_count(&self) -> u128 {
+        self.snapshot().len() as u128
+    }
 }
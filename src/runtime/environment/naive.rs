@@ -1,41 +1,131 @@
-use std::collections::HashMap;
-use std::convert::identity;
+use std::collections::{HashMap, HashSet};
 use std::marker::PhantomData;
-use std::mem::swap;
+use std::mem::{size_of, swap};
 
 use crate::datatypes::coords::{Coordinate, CoordinateBounds};
-use crate::runtime::environment::Environment;
+use crate::runtime::environment::{CapacityEstimate, Environment, TooManyCells};
+use crate::runtime::neighborhood::{NeighborhoodError, Ruleset as NeighborhoodRuleset};
 
+#[derive(Clone)]
 pub struct FixedGrid<C: Coordinate, CB: CoordinateBounds<C>> {
     current_tick: HashMap<C, usize>,
     next_tick: HashMap<C, usize>,
+    age: HashMap<C, usize>,
+    frozen: HashSet<C>,
     neighborhood: Box<[C]>,
-    phantom: PhantomData<CB>,
+    bounds: CB,
+    phantom: PhantomData<C>,
 }
 impl<C: Coordinate, CB: CoordinateBounds<C>> FixedGrid<C, CB> {
+    /// The neighborhood offsets are used as given; expand them with
+    /// `Ruleset::offsets`, or use `from_ruleset`, to have them validated.
     pub fn new(neighborhood: Box<[C]>, bounds: CB) -> Self {
-        let current_tick: HashMap<C, usize> = bounds.into_iter().map(|c| (c, 0)).collect();
+        let current_tick: HashMap<C, usize> = bounds.clone().into_iter().map(|c| (c, 0)).collect();
         let next_tick = HashMap::with_capacity(current_tick.capacity());
         Self {
             current_tick,
             next_tick,
+            age: HashMap::new(),
+            frozen: HashSet::new(),
             neighborhood,
+            bounds,
             phantom: PhantomData,
         }
     }
 
+    /// Like `new`, but refuses bounds covering more than `limit` cells
+    /// instead of trying to allocate them.
+    pub fn with_cell_limit(
+        neighborhood: Box<[C]>,
+        bounds: CB,
+        limit: u128,
+    ) -> Result<Self, TooManyCells> {
+        let cells = bounds.cell_count();
+        if cells > limit {
+            return Err(TooManyCells { cells, limit });
+        }
+        Ok(Self::new(neighborhood, bounds))
+    }
+
+    pub fn bounds(&self) -> &CB {
+        &self.bounds
+    }
+
+    /// The offsets of each cell's neighbors, in the order neighborhoods
+    /// list them
+    pub fn neighborhood(&self) -> &[C] {
+        &self.neighborhood
+    }
+
+    /// The age of every cell, as reported by `get_age`
+    pub fn ages(&self) -> HashMap<C, usize> {
+        self.current_tick
+            .keys()
+            .map(|c| (*c, self.age.get(c).copied().unwrap_or(0)))
+            .collect()
+    }
+
+    /// Overwrite the ages of cells, such as when resuming from a snapshot.
+    /// Cells outside the bounds are ignored.
+    pub fn set_ages(&mut self, ages: HashMap<C, usize>) {
+        let bounds = &self.bounds;
+        self.age
+            .extend(ages.into_iter().filter(|(c, _)| bounds.in_bounds(*c)));
+    }
+
+    pub fn capacity_estimate(&self) -> CapacityEstimate {
+        let cells = self.bounds.cell_count();
+        let stored = self.current_tick.len();
+        // Each hash map entry also costs a control byte
+        let entry = size_of::<C>() + size_of::<usize>() + 1;
+        CapacityEstimate {
+            cells,
+            stored,
+            dense_bytes: cells.saturating_mul(size_of::<usize>() as u128),
+            sparse_bytes: stored as u128 * entry as u128,
+        }
+    }
+
+    /// Replace the neighborhood, taking effect from the next tick. The
+    /// current neighborhood is kept if `rules` is invalid.
+    pub fn set_neighborhood(
+        &mut self,
+        rules: NeighborhoodRuleset<C>,
+    ) -> Result<(), NeighborhoodError>
+    where
+        C: 'static,
+    {
+        self.neighborhood = rules.offsets()?.into_boxed_slice();
+        Ok(())
+    }
+
+    /// Like `from_hashmap`, but takes the neighborhood as rules, which are
+    /// checked with `Ruleset::validate` before being expanded
+    pub fn from_ruleset(
+        rules: NeighborhoodRuleset<C>,
+        hashmap: HashMap<C, usize>,
+        bounds: CB,
+    ) -> Result<Self, NeighborhoodError>
+    where
+        C: 'static,
+    {
+        let neighborhood = rules.offsets()?.into_boxed_slice();
+        Ok(Self::from_hashmap(neighborhood, hashmap, bounds))
+    }
+
     pub fn from_hashmap(neighborhood: Box<[C]>, hashmap: HashMap<C, usize>, bounds: CB) -> Self {
         let mut current_tick = hashmap;
-        for coord in bounds {
-            if !current_tick.contains_key(&coord) {
-                current_tick.insert(coord, 0);
-            }
+        for coord in bounds.clone() {
+            current_tick.entry(coord).or_insert(0);
         }
         let next_tick = HashMap::with_capacity(current_tick.capacity());
         Self {
             current_tick,
             next_tick,
+            age: HashMap::new(),
+            frozen: HashSet::new(),
             neighborhood,
+            bounds,
             phantom: PhantomData,
         }
     }
@@ -47,8 +137,19 @@ impl<C: Coordinate, CB: CoordinateBounds<C>> Environment<C, usize, Vec<usize>, V
         self.next_tick.insert(coord, state);
     }
 
+    fn contains(&self, coord: C) -> bool {
+        self.bounds.in_bounds(coord)
+    }
+
     fn get_state(&self, coord: C) -> Option<usize> {
-        self.current_tick.get(&coord).map(|s| *s)
+        self.current_tick.get(&coord).copied()
+    }
+
+    fn get_age(&self, coord: C) -> Option<usize> {
+        if !self.current_tick.contains_key(&coord) {
+            return None;
+        }
+        Some(self.age.get(&coord).copied().unwrap_or(0))
     }
 
     fn get_neighborhood(&self, coord: C) -> Option<Vec<usize>> {
@@ -59,8 +160,7 @@ impl<C: Coordinate, CB: CoordinateBounds<C>> Environment<C, usize, Vec<usize>, V
             self.neighborhood
                 .iter()
                 .map(|c| coord + *c)
-                .map(|c| self.get_state(c))
-                .filter_map(identity)
+                .filter_map(|c| self.get_state(c))
                 .collect(),
         )
     }
@@ -73,22 +173,52 @@ impl<C: Coordinate, CB: CoordinateBounds<C>> Environment<C, usize, Vec<usize>, V
     }
 
     fn get_schedule(&self) -> Vec<C> {
-        self.current_tick.keys().map(|c| *c).collect()
+        self.current_tick
+            .keys()
+            .filter(|c| !self.frozen.contains(c))
+            .copied()
+            .collect()
+    }
+
+    fn freeze(&mut self, coords: HashSet<C>) {
+        let bounds = &self.bounds;
+        self.frozen
+            .extend(coords.into_iter().filter(|c| bounds.in_bounds(*c)));
+    }
+
+    fn perturb(&mut self, changes: HashMap<C, usize>) {
+        for (coord, state) in changes {
+            if !self.bounds.in_bounds(coord) {
+                continue;
+            }
+            if self.current_tick.insert(coord, state) != Some(state) {
+                self.age.insert(coord, 0);
+            }
+        }
     }
 
     fn snapshot(&self) -> HashMap<C, usize> {
         self.current_tick.clone()
     }
 
+    fn cell_count(&self) -> u128 {
+        self.bounds.cell_count()
+    }
+
     fn tick(&mut self) {
         let Self {
             current_tick,
             next_tick,
+            age,
             ..
         } = self;
         for (k, v) in current_tick.drain() {
-            if !next_tick.contains_key(&k) {
-                next_tick.insert(k, v);
+            let next = *next_tick.entry(k).or_insert(v);
+            let age = age.entry(k).or_insert(0);
+            if next == v {
+                *age += 1;
+            } else {
+                *age = 0;
             }
         }
         swap(&mut self.current_tick, &mut self.next_tick);
@@ -98,7 +228,37 @@ impl<C: Coordinate, CB: CoordinateBounds<C>> Environment<C, usize, Vec<usize>, V
 #[cfg(test)]
 pub mod fixed_grid_test {
     use super::*;
-    use crate::datatypes::coords::Coordinate1D;
+    use crate::datatypes::coords::Dimension;
+    use crate::datatypes::coords::{BoundingBox1D, BoundingBox3D, Coordinate1D, Coordinate3D};
+    use crate::runtime::environment::OutOfBounds;
+    use crate::runtime::neighborhood::Rule as NeighborhoodRule;
+
+    #[test]
+    fn capacity_estimate_of_3d_box() {
+        let env = FixedGrid::<Coordinate3D, BoundingBox3D>::new(
+            vec![].into_boxed_slice(),
+            BoundingBox3D::new((0, 3), (-2, 2), (1, 6)),
+        );
+        let estimate = env.capacity_estimate();
+        assert_eq!(estimate.cells, 4 * 5 * 6);
+        assert_eq!(estimate.stored, 4 * 5 * 6);
+        assert_eq!(estimate.dense_bytes, 4 * 5 * 6 * size_of::<usize>() as u128);
+        assert!(estimate.sparse_bytes > estimate.dense_bytes);
+    }
+
+    #[test]
+    fn with_cell_limit_rejects_large_bounds() {
+        let bounds = BoundingBox1D::new(0, 9);
+        let env = FixedGrid::with_cell_limit(vec![].into_boxed_slice(), bounds, 10).unwrap();
+        assert_eq!(env.get_schedule().len(), 10);
+        assert_eq!(
+            FixedGrid::with_cell_limit(vec![].into_boxed_slice(), bounds, 9).err(),
+            Some(TooManyCells {
+                cells: 10,
+                limit: 9
+            })
+        );
+    }
 
     #[test]
     fn set_state_inserts_into_next_tick() {
@@ -198,4 +358,129 @@ pub mod fixed_grid_test {
         env.current_tick.insert(coord3, 2);
         assert_eq!(env.get_neighborhood(coord3), Some(vec!(0)))
     }
+
+    #[test]
+    fn try_set_state_accepts_in_bounds_coord() {
+        let coord = Coordinate1D::new(0);
+        let mut env = FixedGrid::<Coordinate1D, BoundingBox1D>::new(
+            vec![].into_boxed_slice(),
+            BoundingBox1D::new(-1, 1),
+        );
+        assert_eq!(env.try_set_state(coord, 1), Ok(()));
+        assert_eq!(env.next_tick.get(&coord), Some(&1));
+    }
+
+    #[test]
+    fn try_set_state_rejects_out_of_bounds_coord() {
+        let coord = Coordinate1D::new(2);
+        let mut env = FixedGrid::<Coordinate1D, BoundingBox1D>::new(
+            vec![].into_boxed_slice(),
+            BoundingBox1D::new(-1, 1),
+        );
+        assert_eq!(env.try_set_state(coord, 1), Err(OutOfBounds(coord)));
+        assert!(env.next_tick.is_empty());
+    }
+
+    #[test]
+    fn tick_ages_unchanged_cells() {
+        let coord = Coordinate1D::new(0);
+        let mut env = FixedGrid::<Coordinate1D, Vec<Coordinate1D>>::new(
+            vec![].into_boxed_slice(),
+            vec![coord],
+        );
+        assert_eq!(env.get_age(coord), Some(0));
+        env.tick();
+        env.tick();
+        assert_eq!(env.get_age(coord), Some(2));
+    }
+
+    #[test]
+    fn tick_resets_age_on_transition() {
+        let coord = Coordinate1D::new(0);
+        let mut env = FixedGrid::<Coordinate1D, Vec<Coordinate1D>>::new(
+            vec![].into_boxed_slice(),
+            vec![coord],
+        );
+        env.tick();
+        env.set_state(coord, 1);
+        env.tick();
+        assert_eq!(env.get_age(coord), Some(0));
+    }
+
+    #[test]
+    fn perturb_changes_current_tick() {
+        let coord = Coordinate1D::new(0);
+        let mut env = FixedGrid::<Coordinate1D, Vec<Coordinate1D>>::new(
+            vec![].into_boxed_slice(),
+            vec![coord],
+        );
+        env.tick();
+        env.perturb(vec![(coord, 1)].into_iter().collect());
+        assert_eq!(env.get_state(coord), Some(1));
+        assert_eq!(env.get_age(coord), Some(0));
+        assert!(env.next_tick.is_empty());
+    }
+
+    #[test]
+    fn perturb_ignores_out_of_bounds_cells() {
+        let inside = Coordinate1D::new(0);
+        let outside = Coordinate1D::new(1);
+        let mut env = FixedGrid::<Coordinate1D, Vec<Coordinate1D>>::new(
+            vec![].into_boxed_slice(),
+            vec![inside],
+        );
+        env.perturb(vec![(inside, 1), (outside, 1)].into_iter().collect());
+        assert_eq!(env.get_state(inside), Some(1));
+        assert_eq!(env.get_state(outside), None);
+        assert_eq!(env.get_age(outside), None);
+        assert_eq!(env.snapshot().len(), 1);
+    }
+
+    #[test]
+    fn frozen_cells_are_not_scheduled() {
+        let coord1 = Coordinate1D::new(0);
+        let coord2 = Coordinate1D::new(1);
+        let mut env = FixedGrid::<Coordinate1D, Vec<Coordinate1D>>::new(
+            vec![].into_boxed_slice(),
+            vec![coord1, coord2],
+        );
+        env.freeze(vec![coord1].into_iter().collect());
+        assert_eq!(env.get_schedule(), vec![coord2]);
+    }
+
+    #[test]
+    fn from_ruleset_validates_before_expanding() {
+        let bounds = BoundingBox1D::new(0, 3);
+        let rules =
+            NeighborhoodRuleset::new(vec![NeighborhoodRule::undirected_edge(Dimension::X, 1)]);
+        let env = FixedGrid::from_ruleset(rules, HashMap::new(), bounds).unwrap();
+        assert_eq!(env.get_neighborhood(Coordinate1D::new(0)), Some(vec![0]));
+
+        let rules =
+            NeighborhoodRuleset::new(vec![NeighborhoodRule::undirected_edge(Dimension::Y, 1)]);
+        assert!(matches!(
+            FixedGrid::from_ruleset(rules, HashMap::new(), bounds),
+            Err(NeighborhoodError::DimensionOutOfRange { .. })
+        ));
+        let rules = NeighborhoodRuleset::new(vec![NeighborhoodRule::undirected_circle(
+            Dimension::X,
+            isize::MAX,
+        )]);
+        assert!(matches!(
+            FixedGrid::from_ruleset(rules, HashMap::new(), bounds),
+            Err(NeighborhoodError::TooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn freeze_ignores_out_of_bounds_cells() {
+        let coord = Coordinate1D::new(0);
+        let mut env = FixedGrid::<Coordinate1D, Vec<Coordinate1D>>::new(
+            vec![].into_boxed_slice(),
+            vec![coord],
+        );
+        env.freeze(vec![coord, Coordinate1D::new(5)].into_iter().collect());
+        assert_eq!(env.frozen, vec![coord].into_iter().collect());
+        assert!(env.get_schedule().is_empty());
+    }
 }
@@ -0,0 +1,145 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+
+use crate::datatypes::coords::{Coordinate, CoordinateBounds};
+use crate::runtime::environment::naive::FixedGrid;
+use crate::runtime::environment::Environment;
+
+/// A `FixedGrid` whose neighborhoods pair each neighbor with its distance
+/// from the cell, for rules using `RingCensusNode`.
+///
+/// Distances are Euclidean, rounded up, so a neighbor is within distance
+/// `r` exactly when an `UndirectedCircle` of radius `r` would hold it.
+#[derive(Clone)]
+pub struct RingGrid<C: Coordinate, CB: CoordinateBounds<C>> {
+    grid: FixedGrid<C, CB>,
+    /// Distance of each of the grid's neighborhood offsets, in order
+    distances: Box<[usize]>,
+}
+impl<C: Coordinate, CB: CoordinateBounds<C>> RingGrid<C, CB> {
+    pub fn new(grid: FixedGrid<C, CB>) -> Self {
+        let distances = grid.neighborhood().iter().map(|c| distance(*c)).collect();
+        Self { grid, distances }
+    }
+
+    pub fn grid(&self) -> &FixedGrid<C, CB> {
+        &self.grid
+    }
+
+    pub fn into_inner(self) -> FixedGrid<C, CB> {
+        self.grid
+    }
+}
+impl<C: Coordinate, CB: CoordinateBounds<C>> Environment<C, usize, Vec<(usize, usize)>, Vec<C>>
+    for RingGrid<C, CB>
+{
+    fn set_state(&mut self, coord: C, state: usize) {
+        self.grid.set_state(coord, state);
+    }
+
+    fn contains(&self, coord: C) -> bool {
+        self.grid.contains(coord)
+    }
+
+    fn get_state(&self, coord: C) -> Option<usize> {
+        self.grid.get_state(coord)
+    }
+
+    fn get_age(&self, coord: C) -> Option<usize> {
+        self.grid.get_age(coord)
+    }
+
+    fn get_neighborhood(&self, coord: C) -> Option<Vec<(usize, usize)>> {
+        self.grid.get_state(coord)?;
+        Some(
+            self.grid
+                .neighborhood()
+                .iter()
+                .zip(self.distances.iter())
+                .filter_map(|(c, d)| self.grid.get_state(coord + *c).map(|s| (*d, s)))
+                .collect(),
+        )
+    }
+
+    fn schedule(&mut self, coord: C) {
+        self.grid.schedule(coord);
+    }
+    fn deschedule(&mut self, coord: C) {
+        self.grid.deschedule(coord);
+    }
+
+    fn get_schedule(&self) -> Vec<C> {
+        self.grid.get_schedule()
+    }
+
+    fn freeze(&mut self, coords: HashSet<C>) {
+        self.grid.freeze(coords);
+    }
+
+    fn perturb(&mut self, changes: HashMap<C, usize>) {
+        self.grid.perturb(changes);
+    }
+
+    fn snapshot(&self) -> HashMap<C, usize> {
+        self.grid.snapshot()
+    }
+
+    fn cell_count(&self) -> u128 {
+        self.grid.cell_count()
+    }
+
+    fn tick(&mut self) {
+        self.grid.tick();
+    }
+}
+
+/// Euclidean length of `offset`, rounded up
+fn distance<C: Coordinate>(offset: C) -> usize {
+    let squared = C::dimensionality()
+        .dimensions()
+        .iter()
+        .map(|d| (offset.get(*d).unsigned_abs() as u128).pow(2))
+        .fold(0u128, u128::saturating_add);
+    // Start from the floating point estimate and correct its rounding
+    let mut root = (squared as f64).sqrt() as u128;
+    while root.checked_mul(root).is_none_or(|r| r > squared) {
+        root -= 1;
+    }
+    while root * root < squared {
+        root += 1;
+    }
+    usize::try_from(root).unwrap_or(usize::MAX)
+}
+
+#[cfg(test)]
+pub mod ring_grid_test {
+    use super::*;
+    use crate::datatypes::coords::{BoundingBox1D, Coordinate1D, Coordinate2D, Coordinate3D};
+
+    #[test]
+    fn distance_rounds_up() {
+        assert_eq!(distance(Coordinate2D::new(0, 0)), 0);
+        assert_eq!(distance(Coordinate2D::new(1, 0)), 1);
+        assert_eq!(distance(Coordinate2D::new(1, 1)), 2);
+        assert_eq!(distance(Coordinate2D::new(-3, 4)), 5);
+        assert_eq!(distance(Coordinate3D::new(2, 2, 1)), 3);
+        assert_eq!(distance(Coordinate1D::new(isize::MIN)), 1 << 63);
+    }
+
+    #[test]
+    fn neighborhood_skips_cells_out_of_bounds() {
+        let offsets = vec![Coordinate1D::new(-2), Coordinate1D::new(1)];
+        let mut grid = FixedGrid::new(offsets.into_boxed_slice(), BoundingBox1D::new(0, 2));
+        grid.perturb(vec![(Coordinate1D::new(1), 1)].into_iter().collect());
+        let env = RingGrid::new(grid);
+        assert_eq!(
+            env.get_neighborhood(Coordinate1D::new(0)),
+            Some(vec![(1, 1)])
+        );
+        assert_eq!(
+            env.get_neighborhood(Coordinate1D::new(2)),
+            Some(vec![(2, 0)])
+        );
+        assert_eq!(env.get_neighborhood(Coordinate1D::new(3)), None);
+    }
+}
@@ -1,25 +1,84 @@
+pub mod analysis;
+pub mod batch;
 pub mod environment;
 pub mod neighborhood;
+pub mod output;
+pub mod presets;
+pub mod snapshot;
 pub mod state;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::iter::FromIterator;
 use std::marker::PhantomData;
+use std::time::{Duration, Instant};
 
+use crate::datatypes::coords::{Coordinate, Coordinate2D, CoordinateBounds, Dimensionality};
 use crate::datatypes::ident::Identifer;
 use crate::datatypes::neighborhood::Neighborhood;
 use crate::datatypes::state::State;
-use crate::runtime::environment::Environment;
-use crate::runtime::state::Ruleset;
+use crate::runtime::analysis::{
+    components, detect_spaceships, escaped, hamming_distance, shannon_entropy, state_densities,
+    Spaceship,
+};
+use crate::runtime::environment::naive::FixedGrid;
+use crate::runtime::environment::{Environment, TooManyCells};
+use crate::runtime::output::population_series_json;
+use crate::runtime::snapshot::{Snapshot, SnapshotBounds, SnapshotError};
+use crate::runtime::state::{Cell, RuleError, RuleStateError, Ruleset};
 
 pub trait Runtime<Delta, E> {
     fn run_tick(&mut self) -> Delta;
     fn run_ticks(&mut self, ticks: usize);
     fn environment(&self) -> &E;
+    fn environment_mut(&mut self) -> &mut E;
+    /// Apply external changes to the environment between ticks
+    fn perturb(&mut self, changes: Delta);
+    /// Number of ticks run so far
+    fn generation(&self) -> usize;
 }
 
+/// Timings from `SynchronousRuntime::run_timed`. Per-tick figures are zero
+/// if no ticks were run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunReport {
+    pub ticks: usize,
+    pub total: Duration,
+    pub min_tick: Duration,
+    pub max_tick: Duration,
+    pub mean_tick: Duration,
+    /// Cells not in the default state once the run finished
+    pub live_cells: usize,
+}
+
+/// Transitions made in a tick, with the id of the rule behind each
+type TracedDelta<I, S> = Vec<(I, S, usize)>;
+
+/// Which cells `SynchronousRuntime::run_tick` evaluates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateMode {
+    /// Every cell, every tick
+    Synchronous,
+    /// Alternate halves of a checkerboard: only cells whose parity matches
+    /// the generation's are evaluated, and the rest keep their state. Two
+    /// such ticks update every cell once.
+    Checkerboard,
+}
+
+/// Most cells `SynchronousRuntime::init_from_fn` fills unless configured
+/// otherwise with `with_cell_limit`
+pub const DEFAULT_CELL_LIMIT: u128 = 1 << 24;
+
+#[derive(Clone)]
 pub struct SynchronousRuntime<S: State, N: Neighborhood<S>, E, Schedule> {
     ruleset: Ruleset<S, N>,
     environment: E,
+    generation: usize,
+    allowed_states: Option<HashSet<S>>,
+    update_mode: UpdateMode,
+    /// The part of the schedule a checkerboard tick of the given parity
+    /// evaluates, set by `with_update_mode`
+    checkerboard: Option<fn(&E, usize) -> Schedule>,
+    cell_limit: u128,
     _marker: PhantomData<(Schedule,)>,
 }
 impl<S: State, N: Neighborhood<S>, E, Schedule> SynchronousRuntime<S, N, E, Schedule> {
@@ -27,9 +86,50 @@ impl<S: State, N: Neighborhood<S>, E, Schedule> SynchronousRuntime<S, N, E, Sche
         Self {
             ruleset,
             environment,
+            generation: 0,
+            allowed_states: None,
+            update_mode: UpdateMode::Synchronous,
+            checkerboard: None,
+            cell_limit: DEFAULT_CELL_LIMIT,
             _marker: PhantomData,
         }
     }
+
+    /// Guard against rules producing states outside of `allowed`: any such
+    /// transition moves the cell to the default state instead. Use
+    /// `try_run_tick_with_clamps` to find out which cells were affected.
+    pub fn with_allowed_states(mut self, allowed: HashSet<S>) -> Self {
+        self.allowed_states = Some(allowed);
+        self
+    }
+
+    /// Cap the number of cells `init_from_fn` will fill, in place of
+    /// `DEFAULT_CELL_LIMIT`
+    pub fn with_cell_limit(mut self, limit: u128) -> Self {
+        self.cell_limit = limit;
+        self
+    }
+
+    pub fn update_mode(&self) -> UpdateMode {
+        self.update_mode
+    }
+
+    /// Number of states the ruleset transitions from or to
+    pub fn num_states(&self) -> usize {
+        self.ruleset.num_states()
+    }
+
+    /// Replace the ruleset, keeping the current generation's cells. The
+    /// current ruleset is kept if `rules` refers to undeclared states.
+    pub fn set_rules(
+        &mut self,
+        rules: Ruleset<S, N>,
+        declared: &HashSet<S>,
+    ) -> Result<(), Vec<RuleStateError<S>>> {
+        rules.check_states(declared)?;
+        self.ruleset = rules;
+        Ok(())
+    }
 }
 impl<
         I: Identifer,
@@ -41,32 +141,504 @@ impl<
 {
     // TODO allow for different types of deltas
     fn run_tick(&mut self) -> HashMap<I, S> {
-        let mut delta = HashMap::new();
-        for cell in self.environment.get_schedule() {
-            if let Some(state) = self.ruleset.transition(
-                self.environment
-                    .get_state(cell)
-                    .expect("All scheduled cells should have a state"),
+        self.try_run_tick().expect("Rule evaluation failed")
+    }
+
+    fn run_ticks(&mut self, ticks: usize) {
+        for _ in 0..ticks {
+            self.run_tick();
+        }
+    }
+
+    fn environment(&self) -> &E {
+        &self.environment
+    }
+
+    fn environment_mut(&mut self) -> &mut E {
+        &mut self.environment
+    }
+
+    fn generation(&self) -> usize {
+        self.generation
+    }
+
+    fn perturb(&mut self, changes: HashMap<I, S>) {
+        self.environment.perturb(changes);
+    }
+}
+
+impl<
+        I: Identifer,
+        S: State,
+        N: Neighborhood<S>,
+        Schedule: IntoIterator<Item = I>,
+        E: Environment<I, S, N, Schedule>,
+    > SynchronousRuntime<S, N, E, Schedule>
+{
+    /// Like `run_tick_traced`, but returns an error if a rule fails to
+    /// evaluate. Every rule is evaluated before any cell changes, so on
+    /// error the environment is untouched.
+    fn try_run_tick_traced(&mut self) -> Result<Vec<(I, S, usize)>, RuleError> {
+        Ok(self.try_run_tick_inner()?.0)
+    }
+
+    /// Run a tick, returning the transitions made along with the cells
+    /// whose new state was clamped to the default
+    fn try_run_tick_inner(&mut self) -> Result<(TracedDelta<I, S>, Vec<I>), RuleError> {
+        let schedule = match self.checkerboard {
+            Some(checkerboard) => checkerboard(&self.environment, self.generation % 2),
+            None => self.environment.get_schedule(),
+        };
+        let mut delta = Vec::new();
+        let mut clamped = Vec::new();
+        for cell in schedule {
+            if let Some((state, rule)) = self.ruleset.try_transition_traced(
+                Cell::new(
+                    self.environment
+                        .get_state(cell)
+                        .expect("All scheduled cells should have a state"),
+                    self.environment
+                        .get_age(cell)
+                        .expect("All scheduled cells should have an age"),
+                ),
                 self.environment
                     .get_neighborhood(cell)
                     .expect("All scheduled celss should have a neighborhood"),
-            ) {
-                self.environment.set_state(cell, state);
-                delta.insert(cell, state);
+            )? {
+                let state = match &self.allowed_states {
+                    Some(allowed) if !allowed.contains(&state) => {
+                        clamped.push(cell);
+                        S::default()
+                    }
+                    _ => state,
+                };
+                delta.push((cell, state, rule));
             }
         }
+        for (cell, state, _) in &delta {
+            self.environment.set_state(*cell, *state);
+        }
         self.environment.tick();
-        delta
+        self.generation += 1;
+        Ok((delta, clamped))
     }
 
-    fn run_ticks(&mut self, ticks: usize) {
+    /// Like `run_tick`, but returns an error instead of panicking if a rule
+    /// fails to evaluate, such as on integer overflow. The tick is not run
+    /// in that case.
+    pub fn try_run_tick(&mut self) -> Result<HashMap<I, S>, RuleError> {
+        Ok(self
+            .try_run_tick_traced()?
+            .into_iter()
+            .map(|(ident, state, _)| (ident, state))
+            .collect())
+    }
+
+    /// Like `try_run_tick`, but also returns the cells whose new state was
+    /// outside the allowed states, and so were set to the default state
+    /// instead; see `with_allowed_states`
+    pub fn try_run_tick_with_clamps(&mut self) -> Result<(HashMap<I, S>, Vec<I>), RuleError> {
+        let (delta, clamped) = self.try_run_tick_inner()?;
+        Ok((
+            delta
+                .into_iter()
+                .map(|(ident, state, _)| (ident, state))
+                .collect(),
+            clamped,
+        ))
+    }
+
+    /// Run a tick like `run_tick`, reporting each transition along with
+    /// the id of the rule that fired; see `Ruleset::transition_traced`
+    pub fn run_tick_traced(&mut self) -> Vec<(I, S, usize)> {
+        self.try_run_tick_traced().expect("Rule evaluation failed")
+    }
+
+    /// Replay a tick recorded from `run_tick`: apply its changes as the
+    /// next generation without evaluating any rules
+    pub fn apply_delta(&mut self, delta: &HashMap<I, S>) {
+        for (ident, state) in delta {
+            self.environment.set_state(*ident, *state);
+        }
+        self.environment.tick();
+        self.generation += 1;
+    }
+
+    /// Set the state of every scheduled cell from a function of its
+    /// identifier. Frozen cells are left as they are.
+    ///
+    /// Nothing is changed if the environment holds more cells than the
+    /// runtime's cell limit; see `with_cell_limit`.
+    pub fn init_from_fn<F: Fn(I) -> S>(&mut self, f: F) -> Result<(), TooManyCells> {
+        let cells = self.environment.cell_count();
+        if cells > self.cell_limit {
+            return Err(TooManyCells {
+                cells,
+                limit: self.cell_limit,
+            });
+        }
+        let changes = self
+            .environment
+            .get_schedule()
+            .into_iter()
+            .map(|ident| (ident, f(ident)))
+            .collect();
+        self.environment.perturb(changes);
+        Ok(())
+    }
+
+    /// Hold cells in their current state; see `Environment::freeze`
+    pub fn freeze(&mut self, idents: HashSet<I>) {
+        self.environment.freeze(idents);
+    }
+
+    /// Measure how damage to a single cell spreads. Two copies of this
+    /// runtime are made and `ident` is set to `state` in one of them, then
+    /// both are run for `ticks` ticks. Returns the Hamming distance between
+    /// the two before the first tick and after each tick. This runtime is
+    /// left untouched.
+    pub fn damage_spread(&self, (ident, state): (I, S), ticks: usize) -> Vec<usize>
+    where
+        Self: Clone,
+    {
+        self.damage_spread_many(vec![(ident, state)].into_iter().collect(), ticks)
+    }
+
+    /// Like `damage_spread`, but perturbing any number of cells at once
+    pub fn damage_spread_many(&self, perturbation: HashMap<I, S>, ticks: usize) -> Vec<usize>
+    where
+        Self: Clone,
+    {
+        let mut original = self.clone();
+        let mut twin = self.clone();
+        twin.environment.perturb(perturbation);
+        let mut distances = Vec::with_capacity(ticks + 1);
+        for tick in 0..=ticks {
+            if tick > 0 {
+                original.run_tick();
+                twin.run_tick();
+            }
+            distances.push(hamming_distance(
+                &original.environment.snapshot(),
+                &twin.environment.snapshot(),
+                None,
+            ));
+        }
+        distances
+    }
+
+    /// Run `ticks` ticks, timing each one
+    pub fn run_timed(&mut self, ticks: usize) -> RunReport {
+        let mut min_tick = Duration::MAX;
+        let mut max_tick = Duration::ZERO;
+        let start = Instant::now();
         for _ in 0..ticks {
+            let tick_start = Instant::now();
             self.run_tick();
+            let elapsed = tick_start.elapsed();
+            min_tick = min_tick.min(elapsed);
+            max_tick = max_tick.max(elapsed);
+        }
+        let total = start.elapsed();
+        let live_cells = self
+            .environment
+            .snapshot()
+            .values()
+            .filter(|s| **s != S::default())
+            .count();
+        if ticks == 0 {
+            min_tick = Duration::ZERO;
+        }
+        RunReport {
+            ticks,
+            total,
+            min_tick,
+            max_tick,
+            mean_tick: Duration::from_nanos((total.as_nanos() / ticks.max(1) as u128) as u64),
+            live_cells,
         }
     }
 
-    fn environment(&self) -> &E {
-        &self.environment
+    /// Run `ticks` ticks, returning the size of each tick's delta
+    pub fn run_with_activity(&mut self, ticks: usize) -> Vec<usize> {
+        (0..ticks).map(|_| self.run_tick().len()).collect()
+    }
+
+    /// Run `ticks` ticks, calling `f(tick, ticks)` after every `every`th
+    /// tick. An `every` of zero never reports.
+    pub fn run_with_progress<F: FnMut(usize, usize)>(
+        &mut self,
+        ticks: usize,
+        every: usize,
+        mut f: F,
+    ) {
+        for tick in 1..=ticks {
+            self.run_tick();
+            if every > 0 && tick % every == 0 {
+                f(tick, ticks);
+            }
+        }
+    }
+
+    /// Lazily yield `count` snapshots taken `interval` ticks apart, starting
+    /// with the current state. Ticks are only run as snapshots are pulled.
+    pub fn snapshots_every(
+        &mut self,
+        interval: usize,
+        count: usize,
+    ) -> impl Iterator<Item = HashMap<I, S>> + '_ {
+        (0..count).map(move |i| {
+            if i > 0 {
+                self.run_ticks(interval);
+            }
+            self.environment.snapshot()
+        })
+    }
+
+    /// Run for `ticks` ticks, checking after each that `quantity` of the
+    /// environment still equals its starting value. On the first violation,
+    /// returns the tick along with the expected and actual values.
+    pub fn check_conservation<F: Fn(&HashMap<I, S>) -> i64>(
+        &mut self,
+        quantity: F,
+        ticks: usize,
+    ) -> Result<(), (usize, i64, i64)> {
+        let expected = quantity(&self.environment.snapshot());
+        for tick in 1..=ticks {
+            self.run_tick();
+            let actual = quantity(&self.environment.snapshot());
+            if actual != expected {
+                return Err((tick, expected, actual));
+            }
+        }
+        Ok(())
+    }
+
+    /// For each neighbor count, how many cells have that many neighbors in
+    /// `state`
+    pub fn neighbor_count_histogram(&self, state: S) -> HashMap<usize, usize> {
+        let mut histogram = HashMap::new();
+        for ident in self.environment.snapshot().keys() {
+            let count = self
+                .environment
+                .get_neighborhood(*ident)
+                .expect("All cells should have a neighborhood")
+                .count(state);
+            *histogram.entry(count).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    /// Run `ticks` ticks and render the number of cells in each state,
+    /// before the first tick and after each one, as JSON; see
+    /// `output::population_series_json`
+    pub fn population_series_json(
+        &mut self,
+        ticks: usize,
+        state_names: &HashMap<S, String>,
+    ) -> String {
+        let mut series = Vec::with_capacity(ticks + 1);
+        for tick in 0..=ticks {
+            if tick > 0 {
+                self.run_tick();
+            }
+            let mut counts = HashMap::new();
+            for state in self.environment.snapshot().values() {
+                *counts.entry(*state).or_insert(0) += 1;
+            }
+            series.push(counts);
+        }
+        population_series_json(&series, state_names)
+    }
+
+    /// Shannon entropy, in bits, of the current distribution of states,
+    /// background included
+    pub fn shannon_entropy(&self) -> f64 {
+        shannon_entropy(&self.environment.snapshot())
+    }
+
+    /// Estimate the long-run fraction of cells in each state by running
+    /// `warmup` ticks, then averaging the densities over `sample_ticks` ticks
+    pub fn estimate_equilibrium(&mut self, sample_ticks: usize, warmup: usize) -> HashMap<S, f64> {
+        self.run_ticks(warmup);
+        let mut totals = HashMap::new();
+        for _ in 0..sample_ticks {
+            self.run_tick();
+            for (state, density) in state_densities(&self.environment.snapshot()) {
+                *totals.entry(state).or_insert(0.0) += density;
+            }
+        }
+        for total in totals.values_mut() {
+            *total /= sample_ticks as f64;
+        }
+        totals
+    }
+}
+
+impl<
+        C: Coordinate,
+        S: State,
+        N: Neighborhood<S>,
+        Schedule: IntoIterator<Item = C>,
+        E: Environment<C, S, N, Schedule>,
+    > SynchronousRuntime<S, N, E, Schedule>
+{
+    pub fn dimensionality(&self) -> Dimensionality {
+        C::dimensionality()
+    }
+
+    /// Choose which cells each tick evaluates; see `UpdateMode`
+    pub fn with_update_mode(mut self, mode: UpdateMode) -> Self
+    where
+        Schedule: FromIterator<C>,
+    {
+        self.update_mode = mode;
+        self.checkerboard = match mode {
+            UpdateMode::Synchronous => None,
+            UpdateMode::Checkerboard => Some(|environment: &E, parity| {
+                environment
+                    .get_schedule()
+                    .into_iter()
+                    .filter(|c| c.parity() == parity)
+                    .collect()
+            }),
+        };
+        self
+    }
+
+    /// Whether any non-default cell lies outside of `bounds`
+    pub fn escaped<B: CoordinateBounds<C>>(&self, bounds: &B) -> bool {
+        escaped(&self.environment.snapshot(), bounds)
+    }
+
+    /// Set every cell within `bounds` to `state`. Coordinates outside of
+    /// the environment are ignored.
+    pub fn fill_region<B: CoordinateBounds<C>>(&mut self, bounds: B, state: S) {
+        let changes = bounds
+            .into_iter()
+            .filter(|c| self.environment.contains(*c))
+            .map(|c| (c, state))
+            .collect();
+        self.environment.perturb(changes);
+    }
+
+    /// Set the cells at `pattern`'s offsets from `at` to `state`, so a known
+    /// object can be placed more than once. Coordinates outside of the
+    /// environment are ignored.
+    pub fn place_pattern(&mut self, pattern: &[C], at: C, state: S) {
+        let changes = pattern
+            .iter()
+            .map(|offset| at + *offset)
+            .filter(|c| self.environment.contains(*c))
+            .map(|c| (c, state))
+            .collect();
+        self.environment.perturb(changes);
+    }
+
+    /// Group the current generation's non-default cells into connected
+    /// components; see `analysis::components`
+    pub fn components(&self, neighborhood: &[C]) -> Vec<HashSet<C>> {
+        components(&self.environment.snapshot(), neighborhood)
+    }
+
+    /// Run up to `search_ticks` ticks looking for spaceships among the
+    /// connected components of the current generation; see
+    /// `analysis::detect_spaceships`
+    pub fn detect_spaceships(
+        &mut self,
+        neighborhood: &[C],
+        search_ticks: usize,
+    ) -> Vec<Spaceship<C, S>> {
+        let start = self.environment.snapshot();
+        detect_spaceships(&start, neighborhood, search_ticks, || {
+            self.run_tick();
+            self.environment.snapshot()
+        })
+    }
+
+    /// Run until a non-default cell leaves `bounds`, for at most `max_ticks`
+    /// ticks. Returns the number of ticks run if the pattern escaped.
+    pub fn run_until_escaped<B: CoordinateBounds<C>>(
+        &mut self,
+        bounds: &B,
+        max_ticks: usize,
+    ) -> Option<usize> {
+        for tick in 1..=max_ticks {
+            self.run_tick();
+            if self.escaped(bounds) {
+                return Some(tick);
+            }
+        }
+        None
+    }
+}
+
+impl<
+        S: State,
+        N: Neighborhood<S>,
+        Schedule: IntoIterator<Item = Coordinate2D>,
+        E: Environment<Coordinate2D, S, N, Schedule>,
+    > SynchronousRuntime<S, N, E, Schedule>
+{
+    /// The current generation's cells from top to bottom, then left to
+    /// right; that is, by descending y and then ascending x
+    pub fn iter_row_major(&self) -> impl Iterator<Item = (Coordinate2D, S)> {
+        let mut cells = self.environment.snapshot().into_iter().collect::<Vec<_>>();
+        cells.sort_by_key(|(c, _)| (-c.y(), c.x()));
+        cells.into_iter()
+    }
+
+    /// Mean position of the cells in `state`, or None if there are none
+    pub fn center_of_mass(&self, state: S) -> Option<(f64, f64)> {
+        let (mut x, mut y, mut count) = (0.0, 0.0, 0usize);
+        for (coord, s) in self.environment.snapshot() {
+            if s == state {
+                x += coord.x() as f64;
+                y += coord.y() as f64;
+                count += 1;
+            }
+        }
+        if count == 0 {
+            return None;
+        }
+        Some((x / count as f64, y / count as f64))
+    }
+}
+
+impl<C: Coordinate + Ord, CB: CoordinateBounds<C> + Into<SnapshotBounds>>
+    SynchronousRuntime<usize, Vec<usize>, FixedGrid<C, CB>, Vec<C>>
+{
+    /// Capture the generation, the bounds, the update mode, the allowed
+    /// states, and the state and age of every cell: all that
+    /// `from_snapshot` needs to resume the run exactly
+    pub fn to_snapshot(&self) -> Snapshot<C> {
+        let snapshot = Snapshot::new(self.generation, self.environment.snapshot())
+            .with_bounds(self.environment.bounds().clone().into())
+            .with_ages(self.environment.ages())
+            .with_update_mode(self.update_mode);
+        match &self.allowed_states {
+            Some(allowed) => snapshot.with_allowed_states(allowed.clone()),
+            None => snapshot,
+        }
+    }
+
+    /// Resume a run from a snapshot. `environment` should be freshly built
+    /// with the neighborhood of the run the snapshot came from, and must
+    /// have the same bounds.
+    pub fn from_snapshot(
+        ruleset: Ruleset<usize, Vec<usize>>,
+        mut environment: FixedGrid<C, CB>,
+        snapshot: &Snapshot<C>,
+    ) -> Result<Self, SnapshotError> {
+        if environment.bounds().clone().into() != snapshot.bounds {
+            return Err(SnapshotError::BoundsMismatch);
+        }
+        environment.perturb(snapshot.cells.clone());
+        environment.set_ages(snapshot.ages.clone());
+        let mut runtime = Self::new(ruleset, environment).with_update_mode(snapshot.update_mode);
+        runtime.allowed_states = snapshot.allowed_states.clone();
+        runtime.generation = snapshot.generation;
+        Ok(runtime)
     }
 }
 
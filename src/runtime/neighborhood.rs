@@ -1,10 +1,11 @@
+use std::fmt::Debug;
 use std::iter::Chain;
 use std::marker::PhantomData;
 
 use dyn_clone::DynClone;
 use itertools::Itertools; // unique, cartesian_product
 
-use crate::datatypes::coords::{Coordinate, Dimension, OffsetIterator};
+use crate::datatypes::coords::{Coordinate, Dimension, Dimensionality, OffsetIterator};
 
 #[derive(Debug, Clone)]
 pub struct Ruleset<C> {
@@ -21,6 +22,104 @@ impl<C> Ruleset<C> {
     }
 }
 
+impl<C: Coordinate> Ruleset<C> {
+    /// Check that every rule only names dimensions that `C` has, and that
+    /// the ruleset stays within the default `NeighborhoodLimits`
+    pub fn validate(&self) -> Result<(), NeighborhoodError> {
+        self.validate_with_limits(NeighborhoodLimits::default())
+    }
+
+    pub fn validate_with_limits(
+        &self,
+        limits: NeighborhoodLimits,
+    ) -> Result<(), NeighborhoodError> {
+        let dimensionality = C::dimensionality();
+        let mut size = 0usize;
+        for rule in &self.rules {
+            rule.validate(dimensionality)?;
+            let depth = rule.depth();
+            if depth > limits.max_depth {
+                return Err(NeighborhoodError::TooDeep {
+                    depth,
+                    limit: limits.max_depth,
+                });
+            }
+            size = size.saturating_add(rule.size_bound(dimensionality));
+        }
+        if size > limits.max_neighbors {
+            return Err(NeighborhoodError::TooLarge {
+                size,
+                limit: limits.max_neighbors,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Caps on how large a neighborhood may expand to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NeighborhoodLimits {
+    /// Maximum nesting of compound rules
+    pub max_depth: usize,
+    /// Maximum number of offsets generated, before duplicates are removed
+    pub max_neighbors: usize,
+}
+impl Default for NeighborhoodLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 16,
+            max_neighbors: 1 << 16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NeighborhoodError {
+    DimensionOutOfRange {
+        dimension: Dimension,
+        dimensionality: Dimensionality,
+    },
+    TooDeep {
+        depth: usize,
+        limit: usize,
+    },
+    TooLarge {
+        size: usize,
+        limit: usize,
+    },
+}
+
+impl<C: Coordinate + 'static> Ruleset<C> {
+    /// Expand the ruleset into its offsets, checking it with `validate`
+    /// first so that oversized rules are refused rather than expanded
+    pub fn offsets(&self) -> Result<Vec<C>, NeighborhoodError> {
+        self.offsets_with_limits(NeighborhoodLimits::default())
+    }
+
+    pub fn offsets_with_limits(
+        &self,
+        limits: NeighborhoodLimits,
+    ) -> Result<Vec<C>, NeighborhoodError> {
+        self.validate_with_limits(limits)?;
+        Ok(self.clone().into_iter().collect())
+    }
+
+    /// Largest absolute offset the expanded neighborhood reaches along any
+    /// one dimension; that is, how far influence spreads per tick
+    pub fn max_radius(&self) -> isize {
+        self.clone()
+            .into_iter()
+            .flat_map(|c| {
+                C::dimensionality()
+                    .dimensions()
+                    .iter()
+                    .map(move |d| c.get(*d).abs())
+            })
+            .max()
+            .unwrap_or(0)
+    }
+}
+
 impl<C: Coordinate + 'static> IntoIterator for Ruleset<C> {
     type Item = C;
     type IntoIter = RulesetIterator<C>;
@@ -38,8 +137,7 @@ impl<C: Coordinate + 'static> RulesetIterator<C> {
         let rules_iter = Box::new(
             rules
                 .into_iter()
-                .map(|r| r.iter())
-                .flatten()
+                .flat_map(|r| r.iter())
                 .unique() // Don't double count neighbors
                 .filter(|c| *c != C::default()), // Don't allow origin - no one is their own neighbor
         );
@@ -54,6 +152,15 @@ impl<C> Iterator for RulesetIterator<C> {
     }
 }
 
+/// Extension point for neighborhoods defined in Rust, such as those of
+/// exotic lattices; see `Rule::custom`
+pub trait NeighborGenerator<C>: Debug + DynClone {
+    /// Every offset in the neighborhood of a cell with the given
+    /// dimensionality
+    fn offsets(&self, dimensionality: Dimensionality) -> Vec<C>;
+}
+dyn_clone::clone_trait_object!(<C> NeighborGenerator<C>);
+
 // TODO implement CompoundRule iteratively with Vec<Rule<C>> rather than
 // recursively with left & right rules
 #[derive(Debug, Clone)]
@@ -66,6 +173,8 @@ pub enum Rule<C> {
         dimension: Dimension,
         magnitude: isize,
     },
+    /// Every offset within Euclidean distance `magnitude`, excluding the
+    /// center
     UndirectedCircle {
         dimension: Dimension,
         magnitude: isize,
@@ -74,6 +183,11 @@ pub enum Rule<C> {
         left: Box<Rule<C>>,
         right: Box<Rule<C>>,
     },
+    /// Exactly the listed offsets, for neighborhoods that the other rules
+    /// can't express
+    Explicit(Vec<C>),
+    /// Offsets produced by a user-defined generator
+    Custom(Box<dyn NeighborGenerator<C>>),
 
     Marker(PhantomData<C>),
 }
@@ -85,6 +199,18 @@ impl<C> Rule<C> {
             magnitude,
         }
     }
+    /// An undirected edge for each magnitude in `magnitudes`. Pass a
+    /// stepped range, such as `(2..=6).step_by(2)`, for a lacunary
+    /// neighborhood which samples every few cells.
+    pub fn undirected_edges<M: IntoIterator<Item = isize>>(
+        dimension: Dimension,
+        magnitudes: M,
+    ) -> Vec<Rule<C>> {
+        magnitudes
+            .into_iter()
+            .map(|magnitude| Rule::undirected_edge(dimension, magnitude))
+            .collect()
+    }
     pub fn directed_edge(dimension: Dimension, magnitude: isize) -> Rule<C> {
         Rule::DirectedEdge {
             dimension,
@@ -103,6 +229,75 @@ impl<C> Rule<C> {
             right: Box::new(right),
         }
     }
+
+    pub fn explicit(offsets: Vec<C>) -> Rule<C> {
+        Rule::Explicit(offsets)
+    }
+
+    pub fn custom<G: NeighborGenerator<C> + 'static>(generator: G) -> Rule<C> {
+        Rule::Custom(Box::new(generator))
+    }
+
+    pub fn validate(&self, dimensionality: Dimensionality) -> Result<(), NeighborhoodError> {
+        match self {
+            Rule::UndirectedEdge { dimension, .. }
+            | Rule::DirectedEdge { dimension, .. }
+            | Rule::UndirectedCircle { dimension, .. } => {
+                if dimensionality.has(*dimension) {
+                    Ok(())
+                } else {
+                    Err(NeighborhoodError::DimensionOutOfRange {
+                        dimension: *dimension,
+                        dimensionality,
+                    })
+                }
+            }
+            Rule::CompoundRule { left, right } => {
+                left.validate(dimensionality)?;
+                right.validate(dimensionality)
+            }
+            // Offsets are coordinates of the environment's own type
+            Rule::Explicit(..) | Rule::Custom(..) => Ok(()),
+            Rule::Marker(..) => unreachable!(),
+        }
+    }
+
+    /// Nesting depth of compound rules; a simple rule has depth 1
+    pub fn depth(&self) -> usize {
+        match self {
+            Rule::CompoundRule { left, right } => 1 + left.depth().max(right.depth()),
+            _ => 1,
+        }
+    }
+
+    /// Upper bound on the number of offsets this rule generates, computed
+    /// without expanding it
+    pub fn size_bound(&self, dimensionality: Dimensionality) -> usize {
+        let dimensions = |dimension: &Dimension| match dimension {
+            Dimension::All => dimensionality.dimensions().len(),
+            _ => 1,
+        };
+        match self {
+            Rule::UndirectedEdge { dimension, .. } => 2 * dimensions(dimension),
+            Rule::DirectedEdge { dimension, .. } => dimensions(dimension),
+            Rule::UndirectedCircle {
+                dimension,
+                magnitude,
+            } => {
+                // Every cell of the enclosing cube, minus the center
+                let side = magnitude.unsigned_abs().saturating_mul(2).saturating_add(1);
+                (0..dimensions(dimension))
+                    .fold(1usize, |acc, _| acc.saturating_mul(side))
+                    .saturating_sub(1)
+            }
+            Rule::CompoundRule { left, right } => left
+                .size_bound(dimensionality)
+                .saturating_mul(right.size_bound(dimensionality)),
+            Rule::Explicit(offsets) => offsets.len(),
+            Rule::Custom(generator) => generator.offsets(dimensionality).len(),
+            Rule::Marker(..) => unreachable!(),
+        }
+    }
 }
 
 impl<C: Coordinate + 'static> Rule<C> {
@@ -117,15 +312,37 @@ impl<C: Coordinate + 'static> Rule<C> {
     fn directed_edge_iter(dimension: Dimension, magnitude: isize) -> OffsetIterator<C> {
         C::default().offset(dimension, magnitude)
     }
+    /// Every cell within Euclidean distance `magnitude` of the center,
+    /// excluding the center itself. A single dimension only expands along
+    /// that axis.
     fn undirected_circle_iter(
         dimension: Dimension,
         magnitude: isize,
     ) -> impl Iterator<Item = C> + Clone {
-        // FIXME doesn't actually make a circle yet
-        (-magnitude..0)
-            .chain(1..=magnitude)
-            .map(move |value| C::default().offset(dimension, value))
-            .flatten()
+        // Offsets past isize::MAX can't be represented
+        let radius = magnitude.checked_abs().unwrap_or(isize::MAX);
+        C::dimensionality()
+            .dimensions()
+            .iter()
+            .map(|d| {
+                if dimension == Dimension::All || dimension == *d {
+                    -radius..=radius
+                } else {
+                    0..=0
+                }
+            })
+            .multi_cartesian_product()
+            .filter(|values| values.iter().any(|v| *v != 0))
+            .filter(|values| within_radius(values, radius))
+            .map(|values| {
+                let mut c = C::default();
+                for (d, v) in C::dimensionality().dimensions().iter().zip(values) {
+                    c.set(*d, v);
+                }
+                c
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
     }
     fn compound_rule_iter(left: &Rule<C>, right: &Rule<C>) -> impl Iterator<Item = C> + Clone {
         left.iter()
@@ -146,13 +363,22 @@ impl<C: Coordinate + 'static> Rule<C> {
             Rule::UndirectedCircle {
                 dimension,
                 magnitude,
-            } => Box::new(Self::directed_edge_iter(*dimension, *magnitude)),
+            } => Box::new(Self::undirected_circle_iter(*dimension, *magnitude)),
             Rule::CompoundRule { left, right } => Box::new(Self::compound_rule_iter(left, right)),
+            Rule::Explicit(offsets) => Box::new(offsets.clone().into_iter()),
+            Rule::Custom(generator) => Box::new(generator.offsets(C::dimensionality()).into_iter()),
             Rule::Marker(..) => unreachable!(),
         }
     }
 }
 
+/// Whether `values` lie within Euclidean distance `radius` of the origin.
+/// Squares are taken as u128, which holds the sum of three of them.
+fn within_radius(values: &[isize], radius: isize) -> bool {
+    let square = |v: isize| (v.unsigned_abs() as u128).pow(2);
+    values.iter().map(|v| square(*v)).sum::<u128>() <= square(radius)
+}
+
 trait RuleIterTrait<C: Coordinate>: Iterator<Item = C> + DynClone {}
 dyn_clone::clone_trait_object!(<C> RuleIterTrait<C> where C: Coordinate);
 impl<C: Coordinate, T: Iterator<Item = C> + Clone> RuleIterTrait<C> for T {}
@@ -183,6 +409,208 @@ pub mod test {
         )
     }
 
+    #[test]
+    fn explicit_rule_yields_listed_offsets() {
+        let offsets = vec![
+            Coordinate2D::new(1, 0),
+            Coordinate2D::new(-1, 0),
+            Coordinate2D::new(0, 2),
+        ];
+        let rule: Rule<Coordinate2D> = Rule::explicit(offsets.clone());
+        assert_eq!(rule.size_bound(Dimensionality::TwoDimensional), 3);
+        let ruleset = Ruleset::new(vec![rule]);
+        assert_eq!(ruleset.validate(), Ok(()));
+        assert_eq!(ruleset.into_iter().collect::<Vec<_>>(), offsets);
+    }
+
+    #[test]
+    fn undirected_edges_over_range() {
+        let rules: Vec<Rule<Coordinate2D>> = Rule::undirected_edges(Dimension::X, 1..=3);
+        assert_eq!(rules.len(), 3);
+        assert_eq!(
+            Ruleset::new(rules).into_iter().collect::<HashSet<_>>(),
+            vec![-3, -2, -1, 1, 2, 3]
+                .into_iter()
+                .map(|x| Coordinate2D::new(x, 0))
+                .collect::<HashSet<_>>()
+        );
+    }
+
+    #[test]
+    fn max_radius_of_von_neumann() {
+        let ruleset: Ruleset<Coordinate2D> =
+            Ruleset::new(vec![Rule::undirected_edge(Dimension::All, 1)]);
+        assert_eq!(ruleset.max_radius(), 1);
+    }
+
+    #[test]
+    fn max_radius_of_circle() {
+        let ruleset: Ruleset<Coordinate2D> =
+            Ruleset::new(vec![Rule::undirected_circle(Dimension::All, 3)]);
+        assert_eq!(ruleset.max_radius(), 3);
+    }
+
+    #[test]
+    fn max_radius_of_compound_adds_along_a_dimension() {
+        let ruleset: Ruleset<Coordinate2D> = Ruleset::new(vec![Rule::compound_rule(
+            Rule::undirected_edge(Dimension::X, 1),
+            Rule::undirected_edge(Dimension::X, 2),
+        )]);
+        assert_eq!(ruleset.max_radius(), 3);
+        let empty: Ruleset<Coordinate2D> = Ruleset::new(vec![]);
+        assert_eq!(empty.max_radius(), 0);
+    }
+
+    #[test]
+    fn circle_of_radius_one_is_von_neumann() {
+        let circle: Ruleset<Coordinate2D> =
+            Ruleset::new(vec![Rule::undirected_circle(Dimension::All, 1)]);
+        let von_neumann: Ruleset<Coordinate2D> =
+            Ruleset::new(vec![Rule::undirected_edge(Dimension::All, 1)]);
+        assert_eq!(
+            circle.into_iter().collect::<HashSet<_>>(),
+            von_neumann.into_iter().collect::<HashSet<_>>()
+        );
+    }
+
+    #[test]
+    fn circle_2d_is_euclidean_disc() {
+        let ruleset: Ruleset<Coordinate2D> =
+            Ruleset::new(vec![Rule::undirected_circle(Dimension::All, 2)]);
+        let disc = ruleset.into_iter().collect::<HashSet<_>>();
+        assert_eq!(disc.len(), 12);
+        assert!(disc.contains(&Coordinate2D::new(0, 2)));
+        assert!(disc.contains(&Coordinate2D::new(1, 1)));
+        assert!(!disc.contains(&Coordinate2D::new(1, 2)));
+    }
+
+    #[test]
+    fn circle_3d_is_euclidean_ball() {
+        let ruleset: Ruleset<Coordinate3D> =
+            Ruleset::new(vec![Rule::undirected_circle(Dimension::All, 1)]);
+        assert_eq!(ruleset.into_iter().count(), 6);
+    }
+
+    #[test]
+    fn undirected_circle_1d() {
+        let rule: Rule<Coordinate1D> = Rule::undirected_circle(Dimension::X, 1);
+        assert_eq!(
+            rule.iter().collect::<HashSet<_>>(),
+            vec!(Coordinate1D::new(-1), Coordinate1D::new(1))
+                .into_iter()
+                .collect::<HashSet<_>>()
+        );
+        let rule: Rule<Coordinate1D> = Rule::undirected_circle(Dimension::All, 2);
+        assert_eq!(
+            rule.iter().collect::<HashSet<_>>(),
+            vec![-2, -1, 1, 2]
+                .into_iter()
+                .map(Coordinate1D::new)
+                .collect::<HashSet<_>>()
+        );
+    }
+
+    #[test]
+    fn undirected_circle_2d() {
+        let rule: Rule<Coordinate2D> = Rule::undirected_circle(Dimension::All, 1);
+        assert_eq!(
+            rule.iter().collect::<HashSet<_>>(),
+            vec![(1, 0), (-1, 0), (0, 1), (0, -1)]
+                .into_iter()
+                .map(|(x, y)| Coordinate2D::new(x, y))
+                .collect::<HashSet<_>>()
+        );
+        let rule: Rule<Coordinate2D> = Rule::undirected_circle(Dimension::All, 2);
+        assert_eq!(
+            rule.iter().collect::<HashSet<_>>(),
+            vec![
+                (2, 0),
+                (-2, 0),
+                (0, 2),
+                (0, -2),
+                (1, 0),
+                (-1, 0),
+                (0, 1),
+                (0, -1),
+                (1, 1),
+                (1, -1),
+                (-1, 1),
+                (-1, -1)
+            ]
+            .into_iter()
+            .map(|(x, y)| Coordinate2D::new(x, y))
+            .collect::<HashSet<_>>()
+        );
+    }
+
+    #[test]
+    fn undirected_circle_along_one_dimension() {
+        let rule: Rule<Coordinate2D> = Rule::undirected_circle(Dimension::X, 2);
+        assert_eq!(
+            rule.iter().collect::<HashSet<_>>(),
+            vec![-2, -1, 1, 2]
+                .into_iter()
+                .map(|x| Coordinate2D::new(x, 0))
+                .collect::<HashSet<_>>()
+        );
+        let rule: Rule<Coordinate3D> = Rule::undirected_circle(Dimension::Z, 1);
+        assert_eq!(
+            rule.iter().collect::<HashSet<_>>(),
+            vec![Coordinate3D::new(0, 0, 1), Coordinate3D::new(0, 0, -1)]
+                .into_iter()
+                .collect::<HashSet<_>>()
+        );
+    }
+
+    #[test]
+    fn huge_circles_are_refused_before_expanding() {
+        for magnitude in [isize::MIN, isize::MAX, 4_000_000_000] {
+            let ruleset: Ruleset<Coordinate2D> =
+                Ruleset::new(vec![Rule::undirected_circle(Dimension::All, magnitude)]);
+            assert!(matches!(
+                ruleset.offsets(),
+                Err(NeighborhoodError::TooLarge { .. })
+            ));
+        }
+        let ruleset: Ruleset<Coordinate2D> =
+            Ruleset::new(vec![Rule::undirected_circle(Dimension::All, 2)]);
+        assert_eq!(ruleset.offsets().map(|offsets| offsets.len()), Ok(12));
+    }
+
+    #[test]
+    fn within_radius_does_not_overflow() {
+        assert!(within_radius(&[isize::MIN + 1, 0], isize::MAX));
+        assert!(within_radius(&[isize::MAX, 0, 0], isize::MAX));
+        assert!(!within_radius(&[isize::MAX, 1], isize::MAX));
+        assert!(!within_radius(&[isize::MIN, isize::MIN, isize::MIN], 1));
+        assert!(within_radius(&[3, -4], 5));
+        assert!(!within_radius(&[3, -4], 4));
+    }
+
+    #[test]
+    fn undirected_edges_honor_stride() {
+        let rules: Vec<Rule<Coordinate2D>> =
+            Rule::undirected_edges(Dimension::All, (0..=4).step_by(2));
+        let offsets = Ruleset::new(rules).into_iter().collect::<HashSet<_>>();
+        assert!(!offsets.contains(&Coordinate2D::default()));
+        assert_eq!(
+            offsets,
+            vec![
+                (2, 0),
+                (-2, 0),
+                (4, 0),
+                (-4, 0),
+                (0, 2),
+                (0, -2),
+                (0, 4),
+                (0, -4)
+            ]
+            .into_iter()
+            .map(|(x, y)| Coordinate2D::new(x, y))
+            .collect()
+        );
+    }
+
     #[test]
     fn directed_edge_1d() {
         let rule: Rule<Coordinate1D> = Rule::directed_edge(Dimension::X, 1);
@@ -211,4 +639,92 @@ pub mod test {
             .collect::<HashSet<_>>()
         )
     }
+
+    #[test]
+    fn z_rule_is_invalid_in_2d() {
+        let ruleset: Ruleset<Coordinate2D> = Ruleset::new(vec![Rule::compound_rule(
+            Rule::undirected_edge(Dimension::X, 1),
+            Rule::undirected_edge(Dimension::Z, 1),
+        )]);
+        assert_eq!(
+            ruleset.validate(),
+            Err(NeighborhoodError::DimensionOutOfRange {
+                dimension: Dimension::Z,
+                dimensionality: Dimensionality::TwoDimensional
+            })
+        )
+    }
+
+    #[test]
+    fn z_rule_is_valid_in_3d() {
+        let ruleset: Ruleset<Coordinate3D> = Ruleset::new(vec![Rule::compound_rule(
+            Rule::undirected_edge(Dimension::X, 1),
+            Rule::undirected_edge(Dimension::Z, 1),
+        )]);
+        assert_eq!(ruleset.validate(), Ok(()))
+    }
+
+    #[test]
+    fn all_dimensions_rule_is_valid_in_1d() {
+        let ruleset: Ruleset<Coordinate1D> =
+            Ruleset::new(vec![Rule::undirected_edge(Dimension::All, 1)]);
+        assert_eq!(ruleset.validate(), Ok(()))
+    }
+
+    fn nested_rule(depth: usize) -> Rule<Coordinate2D> {
+        (1..depth).fold(Rule::undirected_edge(Dimension::X, 1), |rule, _| {
+            Rule::compound_rule(rule, Rule::undirected_edge(Dimension::X, 1))
+        })
+    }
+
+    #[test]
+    fn ruleset_within_limits_is_valid() {
+        let limits = NeighborhoodLimits {
+            max_depth: 4,
+            max_neighbors: 16,
+        };
+        let ruleset = Ruleset::new(vec![nested_rule(4)]);
+        assert_eq!(ruleset.validate_with_limits(limits), Ok(()))
+    }
+
+    #[test]
+    fn ruleset_exceeding_neighbor_limit_is_invalid() {
+        let limits = NeighborhoodLimits {
+            max_depth: 4,
+            max_neighbors: 15,
+        };
+        let ruleset = Ruleset::new(vec![nested_rule(4)]);
+        assert_eq!(
+            ruleset.validate_with_limits(limits),
+            Err(NeighborhoodError::TooLarge {
+                size: 16,
+                limit: 15
+            })
+        )
+    }
+
+    #[test]
+    fn ruleset_exceeding_depth_limit_is_invalid() {
+        let limits = NeighborhoodLimits {
+            max_depth: 4,
+            max_neighbors: usize::MAX,
+        };
+        let ruleset = Ruleset::new(vec![nested_rule(5)]);
+        assert_eq!(
+            ruleset.validate_with_limits(limits),
+            Err(NeighborhoodError::TooDeep { depth: 5, limit: 4 })
+        )
+    }
+
+    #[test]
+    fn pathological_ruleset_is_invalid_by_default() {
+        let ruleset: Ruleset<Coordinate2D> = Ruleset::new(vec![Rule::compound_rule(
+            Rule::undirected_circle(Dimension::All, 1000),
+            Rule::undirected_circle(Dimension::All, 1000),
+        )]);
+        assert!(matches!(
+            ruleset.validate(),
+            Err(NeighborhoodError::TooLarge { .. })
+        ))
+    }
 }
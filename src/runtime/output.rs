@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::marker::PhantomData;
+
+use crate::datatypes::coords::{Coordinate, Coordinate1D, Coordinate2D, CoordinateBounds};
+use crate::datatypes::state::State;
+
+/// Error returned when an image would be more than `u32::MAX` pixels wide
+/// or tall, or too large to hold in memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameTooLarge;
+
+/// Writes each tick's delta as one line of JSON:
+/// `{"tick":1,"changes":[[x,y,state],...]}`
+///
+/// Changes are sorted by coordinate so the output is deterministic.
+pub struct JsonlWriter<W: Write> {
+    sink: W,
+}
+
+impl<W: Write> JsonlWriter<W> {
+    pub fn new(sink: W) -> Self {
+        Self { sink }
+    }
+
+    pub fn write_tick<C: Coordinate + Ord, S: State + Display>(
+        &mut self,
+        tick: usize,
+        delta: &HashMap<C, S>,
+    ) -> io::Result<()> {
+        let mut changes = delta.iter().collect::<Vec<_>>();
+        changes.sort();
+        write!(self.sink, "{{\"tick\":{},\"changes\":[", tick)?;
+        for (i, (coord, state)) in changes.into_iter().enumerate() {
+            if i > 0 {
+                write!(self.sink, ",")?;
+            }
+            write!(self.sink, "[")?;
+            for dimension in C::dimensionality().dimensions() {
+                write!(self.sink, "{},", coord.get(*dimension))?;
+            }
+            write!(self.sink, "{}]", state)?;
+        }
+        writeln!(self.sink, "]}}")
+    }
+
+    pub fn into_inner(self) -> W {
+        self.sink
+    }
+}
+
+/// Rasterizes 2D generations into RGBA pixels and hands each frame to a
+/// callback as `(tick, width, height, pixels)`.
+///
+/// The frame spans the bounding box of the cells given, with the top row
+/// holding the greatest y. `palette` chooses each state's colour; pixels
+/// without a cell are left transparent. The pixel buffer is reused between
+/// frames.
+pub struct CallbackOutput<S, P, F> {
+    palette: P,
+    callback: F,
+    pixels: Vec<u8>,
+    _marker: PhantomData<S>,
+}
+
+impl<S: State, P: Fn(S) -> [u8; 4], F: FnMut(usize, u32, u32, &[u8])> CallbackOutput<S, P, F> {
+    pub fn new(palette: P, callback: F) -> Self {
+        Self {
+            palette,
+            callback,
+            pixels: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Rasterize `cells` and pass the frame to the callback. Frames too
+    /// large to describe are refused without calling it.
+    pub fn write_frame(
+        &mut self,
+        tick: usize,
+        cells: &HashMap<Coordinate2D, S>,
+    ) -> Result<(), FrameTooLarge> {
+        self.pixels.clear();
+        let xs = cells.keys().map(|c| c.x());
+        let ys = cells.keys().map(|c| c.y());
+        let (min_x, max_x, min_y, max_y) =
+            match (xs.clone().min(), xs.max(), ys.clone().min(), ys.max()) {
+                (Some(min_x), Some(max_x), Some(min_y), Some(max_y)) => {
+                    (min_x, max_x, min_y, max_y)
+                }
+                _ => {
+                    (self.callback)(tick, 0, 0, &self.pixels);
+                    return Ok(());
+                }
+            };
+        let extent = |min: isize, max: isize| {
+            u32::try_from(max as i128 - min as i128 + 1).map_err(|_| FrameTooLarge)
+        };
+        let (width, height) = (extent(min_x, max_x)?, extent(min_y, max_y)?);
+        let len = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|pixels| pixels.checked_mul(4))
+            .ok_or(FrameTooLarge)?;
+        self.pixels.resize(len, 0);
+        for (coord, state) in cells {
+            let column = (coord.x() as i128 - min_x as i128) as usize;
+            let row = (max_y as i128 - coord.y() as i128) as usize;
+            let offset = (row * width as usize + column) * 4;
+            self.pixels[offset..offset + 4].copy_from_slice(&(self.palette)(*state));
+        }
+        (self.callback)(tick, width, height, &self.pixels);
+        Ok(())
+    }
+}
+
+/// Draws a space-time diagram of a 1D automaton as RGBA pixels: each
+/// generation becomes one row of `cell_width` square cells, with time
+/// running downwards.
+///
+/// Columns follow the iteration order of `bounds`. Cells missing from a
+/// generation are left transparent.
+pub struct SpaceTimeOutput<S, B, P> {
+    bounds: B,
+    palette: P,
+    cell_width: u32,
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+    _marker: PhantomData<S>,
+}
+
+impl<S: State, B: CoordinateBounds<Coordinate1D>, P: Fn(S) -> [u8; 4]> SpaceTimeOutput<S, B, P> {
+    /// Refuses bounds and cell widths whose rows would be more than
+    /// `u32::MAX` pixels wide
+    pub fn new(bounds: B, palette: P, cell_width: u32) -> Result<Self, FrameTooLarge> {
+        let width = u32::try_from(bounds.cell_count())
+            .ok()
+            .and_then(|cells| cells.checked_mul(cell_width))
+            .ok_or(FrameTooLarge)?;
+        Ok(Self {
+            bounds,
+            palette,
+            cell_width,
+            width,
+            height: 0,
+            pixels: Vec::new(),
+            _marker: PhantomData,
+        })
+    }
+
+    /// Append one generation as the next row of cells
+    pub fn write_row(&mut self, cells: &HashMap<Coordinate1D, S>) {
+        let cell_width = self.cell_width as usize;
+        let mut row = vec![0; self.width as usize * 4];
+        for (column, coord) in self.bounds.clone().into_iter().enumerate() {
+            if let Some(state) = cells.get(&coord) {
+                let colour = (self.palette)(*state);
+                let start = column * cell_width * 4;
+                for pixel in row[start..start + cell_width * 4].chunks_mut(4) {
+                    pixel.copy_from_slice(&colour);
+                }
+            }
+        }
+        for _ in 0..cell_width {
+            self.pixels.extend_from_slice(&row);
+        }
+        self.height += self.cell_width;
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+}
+
+/// Render per-tick population counts as a JSON array with one object per
+/// tick, keyed by state name: `[{"DEAD":22,"ALIVE":3},...]`
+///
+/// Every object has a key for every state seen in any tick, in state order.
+/// States missing from `state_names` are named by their `Debug` form.
+pub fn population_series_json<S: State>(
+    series: &[HashMap<S, usize>],
+    state_names: &HashMap<S, String>,
+) -> String {
+    let mut states = series
+        .iter()
+        .flat_map(|counts| counts.keys().copied())
+        .collect::<Vec<_>>();
+    states.sort();
+    states.dedup();
+    let names = states
+        .iter()
+        .map(|state| {
+            state_names
+                .get(state)
+                .cloned()
+                .unwrap_or_else(|| format!("{:?}", state))
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+        })
+        .collect::<Vec<_>>();
+
+    let mut json = String::from("[");
+    for (i, counts) in series.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        json.push('{');
+        for (j, (state, name)) in states.iter().zip(&names).enumerate() {
+            if j > 0 {
+                json.push(',');
+            }
+            let count = counts.get(state).copied().unwrap_or(0);
+            json.push_str(&format!("\"{}\":{}", name, count));
+        }
+        json.push('}');
+    }
+    json.push(']');
+    json
+}
+
+#[cfg(test)]
+pub mod output_tests {
+    use super::*;
+    use crate::datatypes::coords::{BoundingBox1D, Coordinate1D, Coordinate3D};
+
+    #[test]
+    fn jsonl_writes_one_line_per_tick() {
+        let mut writer = JsonlWriter::new(Vec::new());
+        let delta = vec![(Coordinate1D::new(2), 1usize), (Coordinate1D::new(-1), 0)]
+            .into_iter()
+            .collect();
+        writer.write_tick(1, &delta).unwrap();
+        writer
+            .write_tick(2, &HashMap::<Coordinate1D, usize>::new())
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(writer.into_inner()).unwrap(),
+            "{\"tick\":1,\"changes\":[[-1,0],[2,1]]}\n{\"tick\":2,\"changes\":[]}\n"
+        );
+    }
+
+    #[test]
+    fn jsonl_writes_every_dimension() {
+        let mut writer = JsonlWriter::new(Vec::new());
+        let delta = vec![(Coordinate3D::new(1, 2, 3), 4u8)]
+            .into_iter()
+            .collect();
+        writer.write_tick(0, &delta).unwrap();
+        assert_eq!(
+            String::from_utf8(writer.into_inner()).unwrap(),
+            "{\"tick\":0,\"changes\":[[1,2,3,4]]}\n"
+        );
+    }
+
+    #[test]
+    fn population_series_fills_missing_states() {
+        let series = vec![
+            vec![(0usize, 3), (1, 1)].into_iter().collect(),
+            vec![(0usize, 4)].into_iter().collect(),
+        ];
+        let names = vec![(0, "DE\"AD".to_string())].into_iter().collect();
+        assert_eq!(
+            population_series_json(&series, &names),
+            "[{\"DE\\\"AD\":3,\"1\":1},{\"DE\\\"AD\":4,\"1\":0}]"
+        );
+        assert_eq!(population_series_json::<usize>(&[], &names), "[]");
+    }
+
+    #[test]
+    fn space_time_rows_scale_by_cell_width() {
+        let bounds = vec![Coordinate1D::new(0), Coordinate1D::new(1)];
+        let mut output = SpaceTimeOutput::new(bounds, |s: u8| [s, 0, 0, 255], 2).unwrap();
+        let cells = vec![(Coordinate1D::new(1), 9u8)].into_iter().collect();
+        output.write_row(&cells);
+        assert_eq!((output.width(), output.height()), (4, 2));
+        let row = [0, 0, 0, 0, 0, 0, 0, 0, 9, 0, 0, 255, 9, 0, 0, 255];
+        assert_eq!(output.pixels(), [row, row].concat());
+    }
+
+    #[test]
+    fn callback_output_leaves_gaps_transparent() {
+        let mut frames = Vec::new();
+        let mut output = CallbackOutput::new(
+            |s: u8| [s, s, s, 255],
+            |tick, width, height, pixels: &[u8]| {
+                frames.push((tick, width, height, pixels.to_vec()))
+            },
+        );
+        let cells = vec![
+            (Coordinate2D::new(0, 0), 10u8),
+            (Coordinate2D::new(1, 1), 20),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(output.write_frame(3, &cells), Ok(()));
+        assert_eq!(output.write_frame(4, &HashMap::new()), Ok(()));
+        drop(output);
+        assert_eq!(
+            frames,
+            vec![
+                (
+                    3,
+                    2,
+                    2,
+                    vec![0, 0, 0, 0, 20, 20, 20, 255, 10, 10, 10, 255, 0, 0, 0, 0]
+                ),
+                (4, 0, 0, vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn space_time_refuses_rows_wider_than_u32() {
+        let palette = |s: u8| [s, 0, 0, 255];
+        let bounds = BoundingBox1D::new(0, 1 << 16);
+        assert_eq!(
+            SpaceTimeOutput::new(bounds, palette, 1 << 16).err(),
+            Some(FrameTooLarge)
+        );
+        let bounds = BoundingBox1D::new(isize::MIN, isize::MAX);
+        assert_eq!(
+            SpaceTimeOutput::new(bounds, palette, 1).err(),
+            Some(FrameTooLarge)
+        );
+        assert!(SpaceTimeOutput::new(BoundingBox1D::new(0, 9), palette, 3).is_ok());
+    }
+
+    #[test]
+    fn callback_output_refuses_frames_wider_than_u32() {
+        let mut calls = 0;
+        let mut output =
+            CallbackOutput::new(|s: u8| [s, s, s, 255], |_, _, _, _: &[u8]| calls += 1);
+        let cells = vec![
+            (Coordinate2D::new(isize::MIN, 0), 1u8),
+            (Coordinate2D::new(isize::MAX, 0), 1),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(output.write_frame(0, &cells), Err(FrameTooLarge));
+        drop(output);
+        assert_eq!(calls, 0);
+    }
+}
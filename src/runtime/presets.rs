@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+
+use crate::datatypes::coords::{BoundingBox2D, Coordinate2D, Dimension};
+use crate::runtime::environment::naive::FixedGrid;
+use crate::runtime::neighborhood::{Rule as NeighborhoodRule, Ruleset as NeighborhoodRuleset};
+use crate::runtime::state::{
+    ASTNode, ASTRoot, AndNode, CensusNode, EqNode, LoafType, NeqNode, OrNode,
+    Ruleset as StateRuleset,
+};
+use crate::runtime::SynchronousRuntime;
+
+pub const DEAD: usize = 0;
+pub const ALIVE: usize = 1;
+
+pub type LifeLikeRuntime = SynchronousRuntime<
+    usize,
+    Vec<usize>,
+    FixedGrid<Coordinate2D, BoundingBox2D>,
+    Vec<Coordinate2D>,
+>;
+
+/// The 2D Moore neighborhood
+pub fn moore_2d() -> Vec<Coordinate2D> {
+    NeighborhoodRuleset::new(vec![
+        NeighborhoodRule::undirected_edge(Dimension::All, 1),
+        NeighborhoodRule::compound_rule(
+            NeighborhoodRule::undirected_edge(Dimension::X, 1),
+            NeighborhoodRule::undirected_edge(Dimension::Y, 1),
+        ),
+    ])
+    .offsets()
+    .expect("The Moore neighborhood is within the default limits")
+}
+
+/// Build a Life-like automaton in B/S notation, using `DEAD` and `ALIVE`
+/// over a 2D Moore neighborhood. A dead cell is born when its number of
+/// alive neighbors is in `birth`, and a live cell survives when it is in
+/// `survival`.
+pub fn life_like(
+    birth: &[usize],
+    survival: &[usize],
+    bounds: BoundingBox2D,
+    initial_states: HashMap<Coordinate2D, usize>,
+) -> LifeLikeRuntime {
+    let born = birth
+        .iter()
+        .map(|n| census_node(EqNode::new, *n))
+        .reduce(|lhs, rhs| -> LifeNode { OrNode::new(lhs, rhs).boxed() })
+        .unwrap_or_else(|| Box::new(LoafType::Boolean(false)));
+    let dies = survival
+        .iter()
+        .map(|n| census_node(NeqNode::new, *n))
+        .reduce(|lhs, rhs| -> LifeNode { AndNode::new(lhs, rhs).boxed() })
+        .unwrap_or_else(|| Box::new(LoafType::Boolean(true)));
+    let rules = StateRuleset::new(vec![
+        (DEAD, (ASTRoot::new(born), ALIVE)),
+        (ALIVE, (ASTRoot::new(dies), DEAD)),
+    ]);
+    let env = FixedGrid::from_hashmap(moore_2d().into_boxed_slice(), initial_states, bounds);
+    SynchronousRuntime::new(rules, env)
+}
+
+type LifeNode = Box<dyn ASTNode<usize, Vec<usize>>>;
+
+fn census_node<T: ASTNode<usize, Vec<usize>> + 'static>(
+    op: fn(LifeNode, LifeNode) -> T,
+    count: usize,
+) -> LifeNode {
+    Box::new(op(
+        CensusNode::new(ALIVE).boxed(),
+        Box::new(LoafType::from(count)),
+    ))
+}
@@ -0,0 +1,494 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::datatypes::coords::{
+    BoundingBox1D, BoundingBox2D, BoundingBox3D, Circle2D, Coordinate, Coordinate2D, Dimensionality,
+};
+use crate::runtime::UpdateMode;
+
+/// The state of every cell in an environment at a particular generation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot<C: Coordinate> {
+    pub generation: usize,
+    pub bounds: SnapshotBounds,
+    pub cells: HashMap<C, usize>,
+    /// Ticks each cell has spent in its state. Empty if not recorded;
+    /// otherwise cells missing from it are taken to have age zero.
+    pub ages: HashMap<C, usize>,
+    pub update_mode: UpdateMode,
+    /// See `SynchronousRuntime::with_allowed_states`
+    pub allowed_states: Option<HashSet<usize>>,
+}
+
+/// The bounds of the environment a snapshot was taken from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotBounds {
+    Unbounded,
+    Box1D(BoundingBox1D),
+    Box2D(BoundingBox2D),
+    Box3D(BoundingBox3D),
+    Circle2D(Circle2D),
+}
+impl From<BoundingBox1D> for SnapshotBounds {
+    fn from(bounds: BoundingBox1D) -> Self {
+        Self::Box1D(bounds)
+    }
+}
+impl From<BoundingBox2D> for SnapshotBounds {
+    fn from(bounds: BoundingBox2D) -> Self {
+        Self::Box2D(bounds)
+    }
+}
+impl From<BoundingBox3D> for SnapshotBounds {
+    fn from(bounds: BoundingBox3D) -> Self {
+        Self::Box3D(bounds)
+    }
+}
+impl From<Circle2D> for SnapshotBounds {
+    fn from(bounds: Circle2D) -> Self {
+        Self::Circle2D(bounds)
+    }
+}
+impl SnapshotBounds {
+    fn dimensionality(&self) -> Option<Dimensionality> {
+        match self {
+            Self::Unbounded => None,
+            Self::Box1D(..) => Some(Dimensionality::OneDimensional),
+            Self::Box2D(..) | Self::Circle2D(..) => Some(Dimensionality::TwoDimensional),
+            Self::Box3D(..) => Some(Dimensionality::ThreeDimensional),
+        }
+    }
+
+    /// A tag byte followed by the bounds' values as zigzag varints
+    fn write(&self, bytes: &mut Vec<u8>) {
+        let (tag, values) = match self {
+            Self::Unbounded => (0, vec![]),
+            Self::Box1D(b) => (1, vec![b.low(), b.high()]),
+            Self::Box2D(b) => (2, vec![b.x().0, b.x().1, b.y().0, b.y().1]),
+            Self::Box3D(b) => (
+                3,
+                vec![b.x().0, b.x().1, b.y().0, b.y().1, b.z().0, b.z().1],
+            ),
+            Self::Circle2D(c) => (4, vec![c.center().x(), c.center().y(), c.radius()]),
+        };
+        bytes.push(tag);
+        for value in values {
+            write_varint(bytes, zigzag(value));
+        }
+    }
+
+    fn read(cursor: &mut &[u8]) -> Result<Self, SnapshotError> {
+        let (tag, rest) = cursor.split_first().ok_or(SnapshotError::UnexpectedEnd)?;
+        *cursor = rest;
+        let mut next = || read_varint(cursor).map(unzigzag);
+        Ok(match tag {
+            0 => Self::Unbounded,
+            1 => Self::Box1D(BoundingBox1D::new(next()?, next()?)),
+            2 => Self::Box2D(BoundingBox2D::new((next()?, next()?), (next()?, next()?))),
+            3 => Self::Box3D(BoundingBox3D::new(
+                (next()?, next()?),
+                (next()?, next()?),
+                (next()?, next()?),
+            )),
+            4 => Self::Circle2D(Circle2D::new(Coordinate2D::new(next()?, next()?), next()?)),
+            _ => return Err(SnapshotError::UnknownBounds),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotError {
+    UnexpectedEnd,
+    TrailingBytes,
+    VarintOverflow,
+    DimensionalityMismatch,
+    UnknownBounds,
+    InvalidFlag,
+    /// The snapshot was taken from an environment with other bounds
+    BoundsMismatch,
+}
+
+impl<C: Coordinate + Ord> Snapshot<C> {
+    pub fn new(generation: usize, cells: HashMap<C, usize>) -> Self {
+        Self {
+            generation,
+            bounds: SnapshotBounds::Unbounded,
+            cells,
+            ages: HashMap::new(),
+            update_mode: UpdateMode::Synchronous,
+            allowed_states: None,
+        }
+    }
+
+    pub fn with_ages(mut self, ages: HashMap<C, usize>) -> Self {
+        self.ages = ages;
+        self
+    }
+
+    pub fn with_bounds(mut self, bounds: SnapshotBounds) -> Self {
+        self.bounds = bounds;
+        self
+    }
+
+    pub fn with_update_mode(mut self, update_mode: UpdateMode) -> Self {
+        self.update_mode = update_mode;
+        self
+    }
+
+    pub fn with_allowed_states(mut self, allowed_states: HashSet<usize>) -> Self {
+        self.allowed_states = Some(allowed_states);
+        self
+    }
+
+    /// Encode the snapshot in a compact binary format.
+    ///
+    /// The header holds the dimensionality, the bounds, whether ages are
+    /// recorded, the update mode, the allowed states if any (a flag, then
+    /// their number and the states in ascending order), the generation and
+    /// the number of cells. Cells follow in
+    /// sorted order, each as the zigzag varint difference from the previous
+    /// coordinate along every axis, followed by the state and, if recorded,
+    /// the age as varints.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let axes = C::dimensionality().dimensions();
+        let mut bytes = vec![dimensionality_tag(C::dimensionality())];
+        self.bounds.write(&mut bytes);
+        let has_ages = !self.ages.is_empty();
+        bytes.push(has_ages as u8);
+        bytes.push(match self.update_mode {
+            UpdateMode::Synchronous => 0,
+            UpdateMode::Checkerboard => 1,
+        });
+        match &self.allowed_states {
+            None => bytes.push(0),
+            Some(allowed) => {
+                bytes.push(1);
+                let mut sorted = allowed.iter().collect::<Vec<_>>();
+                sorted.sort();
+                write_varint(&mut bytes, sorted.len() as u64);
+                for state in sorted {
+                    write_varint(&mut bytes, *state as u64);
+                }
+            }
+        }
+        write_varint(&mut bytes, self.generation as u64);
+        write_varint(&mut bytes, self.cells.len() as u64);
+
+        let mut sorted = self.cells.iter().collect::<Vec<_>>();
+        sorted.sort();
+        let mut last = C::default();
+        for (coord, state) in sorted {
+            for axis in axes {
+                let delta = coord.get(*axis).wrapping_sub(last.get(*axis));
+                write_varint(&mut bytes, zigzag(delta));
+            }
+            write_varint(&mut bytes, *state as u64);
+            if has_ages {
+                let age = self.ages.get(coord).copied().unwrap_or(0);
+                write_varint(&mut bytes, age as u64);
+            }
+            last = *coord;
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        let mut cursor = bytes;
+        let tag = *cursor.first().ok_or(SnapshotError::UnexpectedEnd)?;
+        cursor = &cursor[1..];
+        if tag != dimensionality_tag(C::dimensionality()) {
+            return Err(SnapshotError::DimensionalityMismatch);
+        }
+        let bounds = SnapshotBounds::read(&mut cursor)?;
+        if bounds
+            .dimensionality()
+            .is_some_and(|d| d != C::dimensionality())
+        {
+            return Err(SnapshotError::DimensionalityMismatch);
+        }
+        let has_ages = read_flag(&mut cursor)?;
+        let update_mode = if read_flag(&mut cursor)? {
+            UpdateMode::Checkerboard
+        } else {
+            UpdateMode::Synchronous
+        };
+        let allowed_states = if read_flag(&mut cursor)? {
+            let len = read_varint(&mut cursor)?;
+            let mut allowed = HashSet::new();
+            for _ in 0..len {
+                allowed.insert(read_varint(&mut cursor)? as usize);
+            }
+            Some(allowed)
+        } else {
+            None
+        };
+        let generation = read_varint(&mut cursor)? as usize;
+        let len = read_varint(&mut cursor)? as usize;
+
+        let axes = C::dimensionality().dimensions();
+        // Every cell takes at least one byte per axis and one for its state,
+        // so don't trust `len` beyond what the input could hold
+        let mut cells = HashMap::with_capacity(len.min(cursor.len() / (axes.len() + 1)));
+        let mut ages = HashMap::new();
+        let mut last = C::default();
+        for _ in 0..len {
+            let mut coord = last;
+            for axis in axes {
+                let delta = unzigzag(read_varint(&mut cursor)?);
+                coord.set(*axis, last.get(*axis).wrapping_add(delta));
+            }
+            let state = read_varint(&mut cursor)? as usize;
+            cells.insert(coord, state);
+            if has_ages {
+                ages.insert(coord, read_varint(&mut cursor)? as usize);
+            }
+            last = coord;
+        }
+        if !cursor.is_empty() {
+            return Err(SnapshotError::TrailingBytes);
+        }
+        Ok(Self {
+            generation,
+            bounds,
+            cells,
+            ages,
+            update_mode,
+            allowed_states,
+        })
+    }
+}
+
+/// A byte that must be either 0 or 1
+fn read_flag(cursor: &mut &[u8]) -> Result<bool, SnapshotError> {
+    let (flag, rest) = cursor.split_first().ok_or(SnapshotError::UnexpectedEnd)?;
+    *cursor = rest;
+    match flag {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(SnapshotError::InvalidFlag),
+    }
+}
+
+fn dimensionality_tag(dimensionality: Dimensionality) -> u8 {
+    match dimensionality {
+        Dimensionality::OneDimensional => 1,
+        Dimensionality::TwoDimensional => 2,
+        Dimensionality::ThreeDimensional => 3,
+    }
+}
+
+fn zigzag(i: isize) -> u64 {
+    let i = i as i64;
+    ((i << 1) ^ (i >> 63)) as u64
+}
+
+fn unzigzag(u: u64) -> isize {
+    ((u >> 1) as i64 ^ -((u & 1) as i64)) as isize
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(cursor: &mut &[u8]) -> Result<u64, SnapshotError> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (byte, rest) = cursor.split_first().ok_or(SnapshotError::UnexpectedEnd)?;
+        *cursor = rest;
+        // The tenth byte only has room for the top bit of a u64
+        if shift == 63 && *byte > 1 {
+            return Err(SnapshotError::VarintOverflow);
+        }
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(SnapshotError::VarintOverflow)
+}
+
+#[cfg(test)]
+pub mod snapshot_tests {
+    use super::*;
+    use crate::datatypes::coords::{BoundingBox2D, Coordinate1D, Coordinate2D, Coordinate3D};
+    use crate::runtime::output::JsonlWriter;
+
+    #[test]
+    fn zigzag_round_trips() {
+        for i in &[0, 1, -1, 63, -64, isize::MAX, isize::MIN] {
+            assert_eq!(unzigzag(zigzag(*i)), *i);
+        }
+    }
+
+    #[test]
+    fn round_trip_1000_cells_2d() {
+        let cells: HashMap<_, _> = BoundingBox2D::new((-20, 19), (-12, 12))
+            .into_iter()
+            .enumerate()
+            .map(|(i, c)| (c, i % 3))
+            .collect();
+        assert_eq!(cells.len(), 1000);
+        let snapshot = Snapshot::new(42, cells);
+        let bytes = snapshot.to_bytes();
+
+        // Compared to the same cells as a line of JSON
+        let mut writer = JsonlWriter::new(Vec::new());
+        writer
+            .write_tick(snapshot.generation, &snapshot.cells)
+            .unwrap();
+        let json = writer.into_inner();
+        assert!(bytes.len() * 3 < json.len());
+
+        assert_eq!(Snapshot::<Coordinate2D>::from_bytes(&bytes), Ok(snapshot));
+    }
+
+    #[test]
+    fn round_trip_1d_and_3d() {
+        let cells = vec![(Coordinate1D::new(-5), 1), (Coordinate1D::new(300), 2)];
+        let snapshot = Snapshot::new(0, cells.into_iter().collect());
+        assert_eq!(
+            Snapshot::<Coordinate1D>::from_bytes(&snapshot.to_bytes()),
+            Ok(snapshot)
+        );
+
+        let cells = vec![
+            (Coordinate3D::new(1, -2, 3), 1),
+            (Coordinate3D::new(-1, 2, -3), 7),
+        ];
+        let snapshot = Snapshot::new(9, cells.into_iter().collect());
+        assert_eq!(
+            Snapshot::<Coordinate3D>::from_bytes(&snapshot.to_bytes()),
+            Ok(snapshot)
+        );
+    }
+
+    #[test]
+    fn round_trip_ages() {
+        let cells = vec![(Coordinate1D::new(0), 1), (Coordinate1D::new(1), 0)];
+        let ages = vec![(Coordinate1D::new(0), 4), (Coordinate1D::new(1), 0)];
+        let snapshot =
+            Snapshot::new(4, cells.into_iter().collect()).with_ages(ages.into_iter().collect());
+        assert_eq!(
+            Snapshot::<Coordinate1D>::from_bytes(&snapshot.to_bytes()),
+            Ok(snapshot)
+        );
+    }
+
+    #[test]
+    fn from_bytes_rejects_wrong_dimensionality() {
+        let snapshot = Snapshot::new(0, vec![(Coordinate1D::new(1), 1)].into_iter().collect());
+        assert_eq!(
+            Snapshot::<Coordinate2D>::from_bytes(&snapshot.to_bytes()),
+            Err(SnapshotError::DimensionalityMismatch)
+        );
+    }
+
+    #[test]
+    fn from_bytes_rejects_truncated_input() {
+        let snapshot = Snapshot::new(0, vec![(Coordinate1D::new(1), 1)].into_iter().collect());
+        let bytes = snapshot.to_bytes();
+        assert_eq!(
+            Snapshot::<Coordinate1D>::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(SnapshotError::UnexpectedEnd)
+        );
+    }
+
+    #[test]
+    fn round_trip_bounds() {
+        let cells: HashMap<_, _> = vec![(Coordinate2D::new(1, 1), 1)].into_iter().collect();
+        for bounds in [
+            SnapshotBounds::Box2D(BoundingBox2D::new((-3, 4), (0, 9))),
+            SnapshotBounds::Circle2D(Circle2D::new(Coordinate2D::new(-7, 2), 5)),
+        ] {
+            let snapshot = Snapshot::new(3, cells.clone()).with_bounds(bounds);
+            assert_eq!(
+                Snapshot::<Coordinate2D>::from_bytes(&snapshot.to_bytes()),
+                Ok(snapshot)
+            );
+        }
+        let snapshot = Snapshot::new(0, HashMap::<Coordinate3D, usize>::new()).with_bounds(
+            SnapshotBounds::Box3D(BoundingBox3D::new((0, 1), (-1, 0), (5, 6))),
+        );
+        assert_eq!(
+            Snapshot::<Coordinate3D>::from_bytes(&snapshot.to_bytes()),
+            Ok(snapshot)
+        );
+    }
+
+    #[test]
+    fn round_trip_update_mode_and_allowed_states() {
+        let cells = vec![(Coordinate1D::new(0), 1)].into_iter().collect();
+        let snapshot = Snapshot::new(7, cells)
+            .with_update_mode(UpdateMode::Checkerboard)
+            .with_allowed_states(vec![0, 1, 300].into_iter().collect());
+        assert_eq!(
+            Snapshot::<Coordinate1D>::from_bytes(&snapshot.to_bytes()),
+            Ok(snapshot)
+        );
+        assert_eq!(
+            Snapshot::<Coordinate1D>::from_bytes(&[1, 0, 0, 2, 0, 0, 0]),
+            Err(SnapshotError::InvalidFlag)
+        );
+    }
+
+    #[test]
+    fn from_bytes_rejects_bounds_of_another_dimensionality() {
+        let snapshot = Snapshot::new(0, HashMap::<Coordinate1D, usize>::new())
+            .with_bounds(SnapshotBounds::Box2D(BoundingBox2D::new((0, 1), (0, 1))));
+        assert_eq!(
+            Snapshot::<Coordinate1D>::from_bytes(&snapshot.to_bytes()),
+            Err(SnapshotError::DimensionalityMismatch)
+        );
+        assert_eq!(
+            Snapshot::<Coordinate1D>::from_bytes(&[1, 9, 0, 0, 0]),
+            Err(SnapshotError::UnknownBounds)
+        );
+    }
+
+    #[test]
+    fn from_bytes_rejects_huge_len() {
+        // 1D, unbounded, no ages, synchronous, no allowed states,
+        // generation 0, then a cell count of u64::MAX
+        let mut bytes = vec![1, 0, 0, 0, 0, 0];
+        write_varint(&mut bytes, u64::MAX);
+        assert_eq!(
+            Snapshot::<Coordinate1D>::from_bytes(&bytes),
+            Err(SnapshotError::UnexpectedEnd)
+        );
+    }
+
+    #[test]
+    fn from_bytes_rejects_truncated_body() {
+        let cells = (0..10).map(|x| (Coordinate2D::new(x, -x), 1)).collect();
+        let bytes = Snapshot::new(0, cells).to_bytes();
+        for end in 0..bytes.len() {
+            assert_eq!(
+                Snapshot::<Coordinate2D>::from_bytes(&bytes[..end]),
+                Err(SnapshotError::UnexpectedEnd)
+            );
+        }
+    }
+
+    #[test]
+    fn read_varint_rejects_overlong_encoding() {
+        let mut max = Vec::new();
+        write_varint(&mut max, u64::MAX);
+        assert_eq!(max.len(), 10);
+        assert_eq!(read_varint(&mut &max[..]), Ok(u64::MAX));
+
+        let mut overlong = max.clone();
+        overlong[9] = 0x02;
+        assert_eq!(
+            read_varint(&mut &overlong[..]),
+            Err(SnapshotError::VarintOverflow)
+        );
+        let eleven = [0xff; 11];
+        assert_eq!(
+            read_varint(&mut &eleven[..]),
+            Err(SnapshotError::VarintOverflow)
+        );
+    }
+}
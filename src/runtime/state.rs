@@ -1,37 +1,331 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::marker::PhantomData;
 use std::ops::{Add, Div, Mul, Sub};
 
-use crate::datatypes::neighborhood::Neighborhood;
+use dyn_clone::DynClone;
+use itertools::Itertools;
+
+use crate::datatypes::coords::{Coordinate, Coordinate2D};
+use crate::datatypes::neighborhood::{Neighborhood, RingNeighborhood};
 use crate::datatypes::state::State;
 
-// TODO Debug, Clone
+// TODO Debug
+
+/// A rule paired with the state it transitions to
+pub type Transition<S, N> = (ASTRoot<S, N>, S);
+
+/// Which states a transition applies to; see `Ruleset::from_selectors`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FromSelector<S: State> {
+    States(Vec<S>),
+    /// Every declared state
+    Any,
+}
 
+/// Transitions for each state, kept in the order they were given. A state
+/// may have several transitions; the first whose rule holds wins.
+#[derive(Clone)]
 pub struct Ruleset<S: State, N: Neighborhood<S>> {
-    rules: HashMap<S, (ASTRoot<S, N>, S)>,
+    rules: HashMap<S, Vec<Transition<S, N>>>,
+    /// Position of each transition in the list given to `new`
+    ids: HashMap<S, Vec<usize>>,
 }
 impl<S: State, N: Neighborhood<S>> Ruleset<S, N> {
-    pub fn new(rules: Vec<(S, (ASTRoot<S, N>, S))>) -> Self {
+    pub fn new(rules: Vec<(S, Transition<S, N>)>) -> Self {
+        let mut by_state: HashMap<S, Vec<Transition<S, N>>> = HashMap::new();
+        let mut ids: HashMap<S, Vec<usize>> = HashMap::new();
+        for (id, (from, transition)) in rules.into_iter().enumerate() {
+            by_state.entry(from).or_default().push(transition);
+            ids.entry(from).or_default().push(id);
+        }
         Self {
-            rules: rules.into_iter().collect(),
+            rules: by_state,
+            ids,
+        }
+    }
+
+    /// Build a ruleset whose transitions may each apply to several states,
+    /// or to every state in `states`. For each state, transitions naming it
+    /// are tried before wildcard ones, each in the order given. Rule ids
+    /// are positions in `rules`, as with `new`.
+    pub fn from_selectors(rules: Vec<(FromSelector<S>, Transition<S, N>)>, states: &[S]) -> Self {
+        let mut ruleset = Self {
+            rules: HashMap::new(),
+            ids: HashMap::new(),
+        };
+        let (specific, wildcard): (Vec<_>, Vec<_>) = rules
+            .into_iter()
+            .enumerate()
+            .partition(|(_, (selector, _))| *selector != FromSelector::Any);
+        for (id, (selector, transition)) in specific.into_iter().chain(wildcard) {
+            let from_states = match selector {
+                FromSelector::States(from_states) => from_states,
+                FromSelector::Any => states.to_vec(),
+            };
+            for from in from_states {
+                ruleset
+                    .rules
+                    .entry(from)
+                    .or_default()
+                    .push(transition.clone());
+                ruleset.ids.entry(from).or_default().push(id);
+            }
+        }
+        ruleset
+    }
+
+    pub fn transition(&self, cell: Cell<S>, neighborhood: N) -> Option<S> {
+        self.transition_traced(cell, neighborhood)
+            .map(|(to_state, _)| to_state)
+    }
+
+    /// Like `transition`, but also returns the id of the rule that fired:
+    /// its position in the list given to `new`
+    pub fn transition_traced(&self, cell: Cell<S>, neighborhood: N) -> Option<(S, usize)> {
+        self.try_transition_traced(cell, neighborhood)
+            .expect("Rule evaluation failed")
+    }
+
+    /// Like `transition`, but returns an error instead of panicking if a
+    /// rule fails to evaluate
+    pub fn try_transition(&self, cell: Cell<S>, neighborhood: N) -> Result<Option<S>, RuleError> {
+        Ok(self
+            .try_transition_traced(cell, neighborhood)?
+            .map(|(to_state, _)| to_state))
+    }
+
+    pub fn try_transition_traced(
+        &self,
+        cell: Cell<S>,
+        neighborhood: N,
+    ) -> Result<Option<(S, usize)>, RuleError> {
+        // A state with no transitions never changes
+        let transitions = match self.rules.get(&cell.state) {
+            Some(transitions) => transitions,
+            None => return Ok(None),
+        };
+        let ids = &self.ids[&cell.state];
+        for ((rule, to_state), id) in transitions.iter().zip(ids) {
+            if rule.try_evaluate(&cell, &neighborhood)? {
+                return Ok(Some((*to_state, *id)));
+            }
         }
+        Ok(None)
     }
 
-    pub fn transition(&self, from_state: S, neighborhood: N) -> Option<S> {
-        let (rule, to_state) = &self.rules[&from_state];
-        if rule.evaluate(neighborhood) {
-            Some(*to_state)
+    /// Check every state the rules refer to against the declared states,
+    /// collecting all errors rather than stopping at the first
+    pub fn check_states(&self, declared: &HashSet<S>) -> Result<(), Vec<RuleStateError<S>>> {
+        let mut from_states = self.rules.keys().copied().collect::<Vec<_>>();
+        from_states.sort();
+        let mut errors = Vec::new();
+        for from in from_states {
+            if !declared.contains(&from) {
+                errors.push(RuleStateError::UnknownFrom(from));
+            }
+            for (rule, to) in &self.rules[&from] {
+                if !declared.contains(to) {
+                    errors.push(RuleStateError::UnknownTo { from, to: *to });
+                }
+                for state in rule.referenced_states() {
+                    if !declared.contains(&state) {
+                        errors.push(RuleStateError::UnknownCensus { from, state });
+                    }
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
         } else {
-            None
+            Err(errors)
         }
     }
+
+    /// Every state that is transitioned from or to, in order
+    pub fn states(&self) -> Vec<S> {
+        let mut states = self
+            .rules
+            .iter()
+            .flat_map(|(from, transitions)| {
+                std::iter::once(*from).chain(transitions.iter().map(|(_, to)| *to))
+            })
+            .collect::<Vec<_>>();
+        states.sort();
+        states.dedup();
+        states
+    }
+
+    /// Number of states that are transitioned from or to
+    pub fn num_states(&self) -> usize {
+        self.states().len()
+    }
+
+    /// Render the transitions as a Graphviz digraph, with a node per state
+    /// and an edge per transition. Edges are labelled with the order their
+    /// rules are tried in. States missing from `state_names` are shown by
+    /// their `Debug` form.
+    pub fn to_dot(&self, state_names: &HashMap<S, String>) -> String {
+        let name = |state: &S| {
+            state_names
+                .get(state)
+                .cloned()
+                .unwrap_or_else(|| format!("{:?}", state))
+                .replace('"', "\\\"")
+        };
+        let mut from_states = self.rules.keys().copied().collect::<Vec<_>>();
+        from_states.sort();
+        let states = self.states();
+
+        let mut dot = String::from("digraph {\n");
+        for state in &states {
+            dot.push_str(&format!("    \"{}\";\n", name(state)));
+        }
+        for from in &from_states {
+            for (priority, (_, to)) in self.rules[from].iter().enumerate() {
+                dot.push_str(&format!(
+                    "    \"{}\" -> \"{}\" [label=\"{}\"];\n",
+                    name(from),
+                    name(to),
+                    priority + 1
+                ));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
 }
 
-pub trait ASTNode<S: State, N: Neighborhood<S>> {
-    fn evaluate(&self, neighborhood: &N) -> LoafType;
+impl<S: State> Ruleset<S, Vec<S>> {
+    /// What a cell in `state` with exactly these neighbors becomes, if its
+    /// rules fire. Meant for unit testing rules without building a grid.
+    pub fn step(&self, state: S, neighborhood: &[S]) -> Option<S> {
+        self.transition(Cell::new(state, 0), neighborhood.to_vec())
+    }
+
+    /// Whether turning any neighbor configuration a quarter turn leaves
+    /// every transition unchanged. `offsets` are the 2D neighbor offsets in
+    /// the order neighborhoods list them; if the turn doesn't map them onto
+    /// themselves, the rules can't be isotropic over them.
+    ///
+    /// Rules that only ever look at neighbor counts are isotropic without
+    /// further checks. Otherwise every assignment of the ruleset's states to
+    /// the neighbors is tried, which is refused if there are more than
+    /// `MAX_ISOTROPY_CONFIGURATIONS` of them.
+    pub fn is_isotropic(&self, offsets: &[Coordinate2D]) -> Result<bool, IsotropyError> {
+        let mut turned = Vec::with_capacity(offsets.len());
+        for offset in offsets {
+            let image = Coordinate2D::new(-offset.y(), offset.x());
+            match offsets.iter().position(|o| *o == image) {
+                Some(index) => turned.push(index),
+                None => return Ok(false),
+            }
+        }
+        if self
+            .rules
+            .values()
+            .flatten()
+            .all(|(rule, _)| rule.order_independent())
+        {
+            return Ok(true);
+        }
+
+        let states = self.states();
+        let configurations = u32::try_from(offsets.len())
+            .ok()
+            .and_then(|n| (states.len() as u128).checked_pow(n))
+            .unwrap_or(u128::MAX);
+        if configurations > MAX_ISOTROPY_CONFIGURATIONS {
+            return Err(IsotropyError::TooManyConfigurations {
+                configurations,
+                limit: MAX_ISOTROPY_CONFIGURATIONS,
+            });
+        }
+        let mut from_states = self.rules.keys().copied().collect::<Vec<_>>();
+        from_states.sort();
+        for from in from_states {
+            for neighbors in (0..offsets.len())
+                .map(|_| states.iter().copied())
+                .multi_cartesian_product()
+            {
+                let mut rotated = neighbors.clone();
+                for (i, state) in neighbors.iter().enumerate() {
+                    rotated[turned[i]] = *state;
+                }
+                let cell = Cell::new(from, 0);
+                if self.try_transition(cell, neighbors)? != self.try_transition(cell, rotated)? {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
 }
 
+/// Most neighbor configurations `Ruleset::is_isotropic` will try
+pub const MAX_ISOTROPY_CONFIGURATIONS: u128 = 1 << 20;
+
+/// Error returned when `Ruleset::is_isotropic` can't decide
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsotropyError {
+    /// A rule failed to evaluate on one of the configurations tried
+    Rule(RuleError),
+    /// The rules read neighbors by position and there are too many
+    /// configurations to try them all
+    TooManyConfigurations { configurations: u128, limit: u128 },
+}
+impl From<RuleError> for IsotropyError {
+    fn from(error: RuleError) -> Self {
+        Self::Rule(error)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleStateError<S: State> {
+    UnknownFrom(S),
+    UnknownTo { from: S, to: S },
+    UnknownCensus { from: S, state: S },
+}
+
+/// The cell a rule is being evaluated for
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Cell<S: State> {
+    pub state: S,
+    /// Number of ticks the cell has spent in its current state
+    pub age: usize,
+}
+impl<S: State> Cell<S> {
+    pub fn new(state: S, age: usize) -> Self {
+        Self { state, age }
+    }
+}
+
+pub trait ASTNode<S: State, N: Neighborhood<S>>: DynClone + Send + Sync {
+    fn evaluate(&self, cell: &Cell<S>, neighborhood: &N) -> LoafType;
+    /// Like `evaluate`, but returns an error instead of panicking. Nodes
+    /// which can fail, or which have children, override this.
+    fn try_evaluate(&self, cell: &Cell<S>, neighborhood: &N) -> Result<LoafType, RuleError> {
+        Ok(self.evaluate(cell, neighborhood))
+    }
+    /// States this node and its children refer to
+    fn referenced_states(&self) -> Vec<S> {
+        Vec::new()
+    }
+    /// Direct children of this node
+    fn children(&self) -> Vec<&dyn ASTNode<S, N>> {
+        Vec::new()
+    }
+    /// Whether this node, leaving aside its children, sees the neighborhood
+    /// only as a multiset of states, so that reordering the neighbors can't
+    /// change its value. Nodes that read neighbors by position must leave
+    /// this false; see `Ruleset::is_isotropic`.
+    fn order_independent(&self) -> bool {
+        false
+    }
+}
+dyn_clone::clone_trait_object!(<S, N> ASTNode<S, N> where S: State, N: Neighborhood<S>);
+
+#[derive(Clone)]
 pub struct ASTRoot<S: State, N: Neighborhood<S>> {
     child: Box<dyn ASTNode<S, N>>,
     phantom: PhantomData<(S, N)>,
@@ -44,11 +338,95 @@ impl<S: State, N: Neighborhood<S>> ASTRoot<S, N> {
         }
     }
 
-    pub fn evaluate(&self, neighborhood: N) -> bool {
-        self.child.evaluate(&neighborhood).into()
+    pub fn evaluate(&self, cell: &Cell<S>, neighborhood: &N) -> bool {
+        self.child.evaluate(cell, neighborhood).into()
+    }
+
+    pub fn try_evaluate(&self, cell: &Cell<S>, neighborhood: &N) -> Result<bool, RuleError> {
+        self.child.try_evaluate(cell, neighborhood)?.boolean()
+    }
+
+    pub fn referenced_states(&self) -> Vec<S> {
+        self.child.referenced_states()
+    }
+
+    /// Whether every node in the tree ignores the order of the
+    /// neighborhood; see `ASTNode::order_independent`
+    pub fn order_independent(&self) -> bool {
+        let mut stack = vec![&*self.child];
+        while let Some(node) = stack.pop() {
+            if !node.order_independent() {
+                return false;
+            }
+            stack.extend(node.children());
+        }
+        true
+    }
+
+    /// Check the tree stays within the default `RuleLimits`
+    pub fn validate(&self) -> Result<(), RuleComplexityError> {
+        self.validate_with_limits(RuleLimits::default())
+    }
+
+    /// Check the tree's depth and node count against `limits`. The tree is
+    /// walked without recursion, so this is safe to call on trees too deep
+    /// to evaluate.
+    pub fn validate_with_limits(&self, limits: RuleLimits) -> Result<(), RuleComplexityError> {
+        let mut stack = vec![(&*self.child, 1)];
+        let mut nodes = 0usize;
+        while let Some((node, depth)) = stack.pop() {
+            nodes += 1;
+            if depth > limits.max_depth {
+                return Err(RuleComplexityError::TooDeep {
+                    depth,
+                    limit: limits.max_depth,
+                });
+            }
+            if nodes > limits.max_nodes {
+                return Err(RuleComplexityError::TooLarge {
+                    nodes,
+                    limit: limits.max_nodes,
+                });
+            }
+            stack.extend(node.children().into_iter().map(|child| (child, depth + 1)));
+        }
+        Ok(())
+    }
+}
+
+/// Caps on how complex a rule's expression may be
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RuleLimits {
+    /// Maximum nesting of nodes; a lone leaf has depth 1
+    pub max_depth: usize,
+    /// Maximum number of nodes in the tree
+    pub max_nodes: usize,
+}
+impl Default for RuleLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 256,
+            max_nodes: 1 << 16,
+        }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleComplexityError {
+    TooDeep { depth: usize, limit: usize },
+    TooLarge { nodes: usize, limit: usize },
+}
+
+/// Error returned when a rule fails to evaluate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleError {
+    /// Integer arithmetic overflowed
+    Overflow,
+    DivisionByZero,
+    /// A boolean was used where an integer was expected, or vice versa
+    TypeMismatch,
+}
+
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug)]
 pub enum LoafType {
     Boolean(bool),
@@ -58,18 +436,60 @@ impl LoafType {
     fn boxed(self) -> Box<Self> {
         Box::new(self)
     }
-}
-impl Into<bool> for LoafType {
-    fn into(self) -> bool {
+
+    pub fn boolean(self) -> Result<bool, RuleError> {
+        match self {
+            Self::Boolean(b) => Ok(b),
+            _ => Err(RuleError::TypeMismatch),
+        }
+    }
+    pub fn integer(self) -> Result<isize, RuleError> {
         match self {
+            Self::Integer(i) => Ok(i),
+            _ => Err(RuleError::TypeMismatch),
+        }
+    }
+
+    /// A count of neighbors as an integer, or Overflow if it doesn't fit
+    pub fn count(count: usize) -> Result<LoafType, RuleError> {
+        isize::try_from(count)
+            .map(Self::Integer)
+            .map_err(|_| RuleError::Overflow)
+    }
+
+    pub fn checked_add(self, rhs: LoafType) -> Result<LoafType, RuleError> {
+        let sum = self.integer()?.checked_add(rhs.integer()?);
+        sum.map(Self::Integer).ok_or(RuleError::Overflow)
+    }
+    pub fn checked_sub(self, rhs: LoafType) -> Result<LoafType, RuleError> {
+        let difference = self.integer()?.checked_sub(rhs.integer()?);
+        difference.map(Self::Integer).ok_or(RuleError::Overflow)
+    }
+    pub fn checked_mul(self, rhs: LoafType) -> Result<LoafType, RuleError> {
+        let product = self.integer()?.checked_mul(rhs.integer()?);
+        product.map(Self::Integer).ok_or(RuleError::Overflow)
+    }
+    pub fn checked_div(self, rhs: LoafType) -> Result<LoafType, RuleError> {
+        let (a, b) = (self.integer()?, rhs.integer()?);
+        if b == 0 {
+            return Err(RuleError::DivisionByZero);
+        }
+        a.checked_div(b)
+            .map(Self::Integer)
+            .ok_or(RuleError::Overflow)
+    }
+}
+impl From<LoafType> for bool {
+    fn from(value: LoafType) -> Self {
+        match value {
             LoafType::Boolean(b) => b,
             _ => panic!("Attempted to cast a nonboolean to bool"),
         }
     }
 }
-impl Into<isize> for LoafType {
-    fn into(self) -> isize {
-        match self {
+impl From<LoafType> for isize {
+    fn from(value: LoafType) -> Self {
+        match value {
             LoafType::Integer(i) => i,
             _ => panic!("Attempted to cast a noninteger to integer"),
         }
@@ -150,13 +570,17 @@ impl Div<LoafType> for LoafType {
     }
 }
 impl<S: State, N: Neighborhood<S>> ASTNode<S, N> for LoafType {
-    fn evaluate(&self, _neighborhood: &N) -> LoafType {
+    fn evaluate(&self, _cell: &Cell<S>, _neighborhood: &N) -> LoafType {
         *self
     }
+    fn order_independent(&self) -> bool {
+        true
+    }
 }
 
 macro_rules! binary_operations {
     ( $($name:ident : $logic:expr)* ) => {$(
+        #[derive(Clone)]
         pub struct $name<S: State, N: Neighborhood<S>> {
             lhs: Box<dyn ASTNode<S, N>>,
             rhs: Box<dyn ASTNode<S, N>>
@@ -170,29 +594,80 @@ macro_rules! binary_operations {
             }
         }
         impl<S: State, N: Neighborhood<S>> ASTNode<S, N> for $name<S, N> {
-            fn evaluate(&self, neighborhood: &N) -> LoafType {
-                let f: fn(LoafType, LoafType) -> LoafType = $logic;
-                (f)(self.lhs.evaluate(&neighborhood), self.rhs.evaluate(&neighborhood))
+            fn evaluate(&self, cell: &Cell<S>, neighborhood: &N) -> LoafType {
+                self.try_evaluate(cell, neighborhood).expect("Rule evaluation failed")
+            }
+            fn try_evaluate(&self, cell: &Cell<S>, neighborhood: &N) -> Result<LoafType, RuleError> {
+                let f: fn(LoafType, LoafType) -> Result<LoafType, RuleError> = $logic;
+                (f)(
+                    self.lhs.try_evaluate(cell, neighborhood)?,
+                    self.rhs.try_evaluate(cell, neighborhood)?,
+                )
+            }
+            fn referenced_states(&self) -> Vec<S> {
+                let mut states = self.lhs.referenced_states();
+                states.extend(self.rhs.referenced_states());
+                states
+            }
+            fn children(&self) -> Vec<&dyn ASTNode<S, N>> {
+                vec![&*self.lhs, &*self.rhs]
+            }
+            fn order_independent(&self) -> bool {
+                true
             }
         }
     )*}
 }
 
 binary_operations!(
-    AddNode: |lhs, rhs| lhs + rhs
-    SubNode: |lhs, rhs| lhs - rhs
-    MulNode: |lhs, rhs| lhs * rhs
-    DivNode: |lhs, rhs| lhs / rhs
-    EqNode: |lhs, rhs| (lhs == rhs).into()
-    NeqNode: |lhs, rhs| (lhs != rhs).into()
-    GtNode: |lhs, rhs| (lhs > rhs).into()
-    GteNode: |lhs, rhs| (lhs >= rhs).into()
-    LtNode: |lhs, rhs| (lhs < rhs).into()
-    LteNode: |lhs, rhs| (lhs <= rhs).into()
-    AndNode: |lhs, rhs| (lhs.into() && rhs.into()).into()
-    OrNode: |lhs, rhs| (lhs.into() || rhs.into()).into()
+    AddNode: |lhs, rhs| lhs.checked_add(rhs)
+    SubNode: |lhs, rhs| lhs.checked_sub(rhs)
+    MulNode: |lhs, rhs| lhs.checked_mul(rhs)
+    DivNode: |lhs, rhs| lhs.checked_div(rhs)
+    EqNode: |lhs, rhs| Ok((lhs == rhs).into())
+    NeqNode: |lhs, rhs| Ok((lhs != rhs).into())
+    GtNode: |lhs, rhs| Ok((lhs > rhs).into())
+    GteNode: |lhs, rhs| Ok((lhs >= rhs).into())
+    LtNode: |lhs, rhs| Ok((lhs < rhs).into())
+    LteNode: |lhs, rhs| Ok((lhs <= rhs).into())
+    AndNode: |lhs, rhs| Ok((lhs.boolean()? && rhs.boolean()?).into())
+    OrNode: |lhs, rhs| Ok((lhs.boolean()? || rhs.boolean()?).into())
+    MinNode: |lhs, rhs| Ok(lhs.integer()?.min(rhs.integer()?).into())
+    MaxNode: |lhs, rhs| Ok(lhs.integer()?.max(rhs.integer()?).into())
 );
 
+/// Logical negation of its child, which must evaluate to a boolean
+#[derive(Clone)]
+pub struct NotNode<S: State, N: Neighborhood<S>> {
+    child: Box<dyn ASTNode<S, N>>,
+}
+impl<S: State, N: Neighborhood<S>> NotNode<S, N> {
+    pub fn new(child: Box<dyn ASTNode<S, N>>) -> Self {
+        Self { child }
+    }
+    pub fn boxed(self) -> Box<Self> {
+        Box::new(self)
+    }
+}
+impl<S: State, N: Neighborhood<S>> ASTNode<S, N> for NotNode<S, N> {
+    fn evaluate(&self, cell: &Cell<S>, neighborhood: &N) -> LoafType {
+        self.try_evaluate(cell, neighborhood)
+            .expect("Rule evaluation failed")
+    }
+    fn try_evaluate(&self, cell: &Cell<S>, neighborhood: &N) -> Result<LoafType, RuleError> {
+        Ok((!self.child.try_evaluate(cell, neighborhood)?.boolean()?).into())
+    }
+    fn referenced_states(&self) -> Vec<S> {
+        self.child.referenced_states()
+    }
+    fn children(&self) -> Vec<&dyn ASTNode<S, N>> {
+        vec![&*self.child]
+    }
+    fn order_independent(&self) -> bool {
+        true
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CensusNode<S: State> {
     state: S,
@@ -205,9 +680,183 @@ impl<S: State> CensusNode<S> {
         Box::new(self)
     }
 }
-impl<S: State, N: Neighborhood<S>> ASTNode<S, N> for CensusNode<S> {
-    fn evaluate(&self, neighborhood: &N) -> LoafType {
-        neighborhood.count(self.state).into()
+impl<S: State + Send + Sync, N: Neighborhood<S>> ASTNode<S, N> for CensusNode<S> {
+    fn evaluate(&self, cell: &Cell<S>, neighborhood: &N) -> LoafType {
+        self.try_evaluate(cell, neighborhood)
+            .expect("Rule evaluation failed")
+    }
+    fn try_evaluate(&self, _cell: &Cell<S>, neighborhood: &N) -> Result<LoafType, RuleError> {
+        LoafType::count(neighborhood.count(self.state))
+    }
+    fn referenced_states(&self) -> Vec<S> {
+        vec![self.state]
+    }
+    fn order_independent(&self) -> bool {
+        true
+    }
+}
+
+/// Counts every neighbor, regardless of state
+#[derive(Debug, Clone, Copy)]
+pub struct TotalCensusNode;
+impl TotalCensusNode {
+    pub fn boxed(self) -> Box<Self> {
+        Box::new(self)
+    }
+}
+impl<S: State, N: Neighborhood<S>> ASTNode<S, N> for TotalCensusNode {
+    fn evaluate(&self, cell: &Cell<S>, neighborhood: &N) -> LoafType {
+        self.try_evaluate(cell, neighborhood)
+            .expect("Rule evaluation failed")
+    }
+    fn try_evaluate(&self, _cell: &Cell<S>, neighborhood: &N) -> Result<LoafType, RuleError> {
+        LoafType::count(neighborhood.total())
+    }
+    fn order_independent(&self) -> bool {
+        true
+    }
+}
+
+/// Sums the number of neighbors in each state, scaled by that state's
+/// weight. States without a weight count for nothing.
+#[derive(Debug, Clone)]
+pub struct WeightedCensusNode<S: State> {
+    weights: Vec<(S, isize)>,
+}
+impl<S: State> WeightedCensusNode<S> {
+    pub fn new(weights: Vec<(S, isize)>) -> Self {
+        Self { weights }
+    }
+    pub fn boxed(self) -> Box<Self> {
+        Box::new(self)
+    }
+}
+impl<S: State + Send + Sync, N: Neighborhood<S>> ASTNode<S, N> for WeightedCensusNode<S> {
+    fn evaluate(&self, cell: &Cell<S>, neighborhood: &N) -> LoafType {
+        self.try_evaluate(cell, neighborhood)
+            .expect("Rule evaluation failed")
+    }
+    fn try_evaluate(&self, _cell: &Cell<S>, neighborhood: &N) -> Result<LoafType, RuleError> {
+        self.weights
+            .iter()
+            .try_fold(LoafType::Integer(0), |sum, (state, weight)| {
+                let term =
+                    LoafType::count(neighborhood.count(*state))?.checked_mul((*weight).into())?;
+                sum.checked_add(term)
+            })
+    }
+    fn referenced_states(&self) -> Vec<S> {
+        self.weights.iter().map(|(state, _)| *state).collect()
+    }
+    fn order_independent(&self) -> bool {
+        true
+    }
+}
+
+/// Counts neighbors in a state whose distance from the cell is within
+/// `inner..=outer`
+#[derive(Debug, Clone)]
+pub struct RingCensusNode<S: State> {
+    state: S,
+    inner: usize,
+    outer: usize,
+}
+impl<S: State> RingCensusNode<S> {
+    pub fn new(state: S, inner: usize, outer: usize) -> Self {
+        Self {
+            state,
+            inner,
+            outer,
+        }
+    }
+    pub fn boxed(self) -> Box<Self> {
+        Box::new(self)
+    }
+}
+impl<S: State + Send + Sync, N: RingNeighborhood<S>> ASTNode<S, N> for RingCensusNode<S> {
+    fn evaluate(&self, cell: &Cell<S>, neighborhood: &N) -> LoafType {
+        self.try_evaluate(cell, neighborhood)
+            .expect("Rule evaluation failed")
+    }
+    fn try_evaluate(&self, _cell: &Cell<S>, neighborhood: &N) -> Result<LoafType, RuleError> {
+        LoafType::count(neighborhood.count_in_ring(self.state, self.inner, self.outer))
+    }
+    fn referenced_states(&self) -> Vec<S> {
+        vec![self.state]
+    }
+}
+
+/// Evaluates to the greatest state among the neighbors, or -1 if there are
+/// no neighbors
+#[derive(Debug, Clone, Copy)]
+pub struct MaxNeighborNode;
+impl MaxNeighborNode {
+    pub fn boxed(self) -> Box<Self> {
+        Box::new(self)
+    }
+}
+impl<S: State + Into<LoafType>, N: Neighborhood<S>> ASTNode<S, N> for MaxNeighborNode {
+    fn evaluate(&self, _cell: &Cell<S>, neighborhood: &N) -> LoafType {
+        neighborhood
+            .max_state()
+            .map_or(LoafType::Integer(-1), Into::into)
+    }
+    fn order_independent(&self) -> bool {
+        true
+    }
+}
+
+/// Evaluates to the least state among the neighbors, or -1 if there are no
+/// neighbors
+#[derive(Debug, Clone, Copy)]
+pub struct MinNeighborNode;
+impl MinNeighborNode {
+    pub fn boxed(self) -> Box<Self> {
+        Box::new(self)
+    }
+}
+impl<S: State + Into<LoafType>, N: Neighborhood<S>> ASTNode<S, N> for MinNeighborNode {
+    fn evaluate(&self, _cell: &Cell<S>, neighborhood: &N) -> LoafType {
+        neighborhood
+            .min_state()
+            .map_or(LoafType::Integer(-1), Into::into)
+    }
+    fn order_independent(&self) -> bool {
+        true
+    }
+}
+
+/// Evaluates to the cell's own state
+#[derive(Debug, Clone, Copy)]
+pub struct SelfNode;
+impl SelfNode {
+    pub fn boxed(self) -> Box<Self> {
+        Box::new(self)
+    }
+}
+impl<S: State + Into<LoafType>, N: Neighborhood<S>> ASTNode<S, N> for SelfNode {
+    fn evaluate(&self, cell: &Cell<S>, _neighborhood: &N) -> LoafType {
+        cell.state.into()
+    }
+    fn order_independent(&self) -> bool {
+        true
+    }
+}
+
+/// Evaluates to the number of ticks the cell has spent in its current state
+#[derive(Debug, Clone, Copy)]
+pub struct AgeNode;
+impl AgeNode {
+    pub fn boxed(self) -> Box<Self> {
+        Box::new(self)
+    }
+}
+impl<S: State, N: Neighborhood<S>> ASTNode<S, N> for AgeNode {
+    fn evaluate(&self, cell: &Cell<S>, _neighborhood: &N) -> LoafType {
+        cell.age.into()
+    }
+    fn order_independent(&self) -> bool {
+        true
     }
 }
 
@@ -251,7 +900,7 @@ pub mod state_rules_tests {
     fn add_rule() {
         assert_eq!(
             AddNode::new(LoafType::Integer(10).boxed(), LoafType::Integer(5).boxed())
-                .evaluate(&Vec::<usize>::new()),
+                .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Integer(15)
         )
     }
@@ -260,7 +909,7 @@ pub mod state_rules_tests {
     fn sub_rule() {
         assert_eq!(
             SubNode::new(LoafType::Integer(10).boxed(), LoafType::Integer(5).boxed())
-                .evaluate(&Vec::<usize>::new()),
+                .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Integer(5)
         )
     }
@@ -269,7 +918,7 @@ pub mod state_rules_tests {
     fn mul_rule() {
         assert_eq!(
             MulNode::new(LoafType::Integer(10).boxed(), LoafType::Integer(5).boxed())
-                .evaluate(&Vec::<usize>::new()),
+                .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Integer(50)
         )
     }
@@ -278,7 +927,7 @@ pub mod state_rules_tests {
     fn div_rule() {
         assert_eq!(
             DivNode::new(LoafType::Integer(10).boxed(), LoafType::Integer(5).boxed())
-                .evaluate(&Vec::<usize>::new()),
+                .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Integer(2)
         )
     }
@@ -287,7 +936,7 @@ pub mod state_rules_tests {
     fn gt_rule_true_when_lhs_gt_rhs() {
         assert_eq!(
             GtNode::new(LoafType::Integer(10).boxed(), LoafType::Integer(5).boxed())
-                .evaluate(&Vec::<usize>::new()),
+                .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Boolean(true)
         );
     }
@@ -295,7 +944,7 @@ pub mod state_rules_tests {
     fn gt_rule_false_when_lhs_lt_rhs() {
         assert_eq!(
             GtNode::new(LoafType::Integer(5).boxed(), LoafType::Integer(10).boxed())
-                .evaluate(&Vec::<usize>::new()),
+                .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Boolean(false)
         );
     }
@@ -303,7 +952,7 @@ pub mod state_rules_tests {
     fn gt_rule_false_when_lhs_eq_rhs() {
         assert_eq!(
             GtNode::new(LoafType::Integer(10).boxed(), LoafType::Integer(10).boxed())
-                .evaluate(&Vec::<usize>::new()),
+                .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Boolean(false)
         );
     }
@@ -312,7 +961,7 @@ pub mod state_rules_tests {
     fn gte_rule_true_when_lhs_gt_rhs() {
         assert_eq!(
             GteNode::new(LoafType::Integer(10).boxed(), LoafType::Integer(5).boxed())
-                .evaluate(&Vec::<usize>::new()),
+                .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Boolean(true)
         );
     }
@@ -320,7 +969,7 @@ pub mod state_rules_tests {
     fn gte_rule_false_when_lhs_lt_rhs() {
         assert_eq!(
             GteNode::new(LoafType::Integer(5).boxed(), LoafType::Integer(10).boxed())
-                .evaluate(&Vec::<usize>::new()),
+                .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Boolean(false)
         );
     }
@@ -328,7 +977,7 @@ pub mod state_rules_tests {
     fn gte_rule_true_when_lhs_eq_rhs() {
         assert_eq!(
             GteNode::new(LoafType::Integer(10).boxed(), LoafType::Integer(10).boxed())
-                .evaluate(&Vec::<usize>::new()),
+                .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Boolean(true)
         );
     }
@@ -337,7 +986,7 @@ pub mod state_rules_tests {
     fn lt_rule_false_when_lhs_gt_rhs() {
         assert_eq!(
             LtNode::new(LoafType::Integer(10).boxed(), LoafType::Integer(5).boxed())
-                .evaluate(&Vec::<usize>::new()),
+                .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Boolean(false)
         );
     }
@@ -345,7 +994,7 @@ pub mod state_rules_tests {
     fn lt_rule_false_when_lhs_lt_rhs() {
         assert_eq!(
             LtNode::new(LoafType::Integer(5).boxed(), LoafType::Integer(10).boxed())
-                .evaluate(&Vec::<usize>::new()),
+                .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Boolean(true)
         );
     }
@@ -353,7 +1002,7 @@ pub mod state_rules_tests {
     fn lt_rule_false_when_lhs_eq_rhs() {
         assert_eq!(
             LtNode::new(LoafType::Integer(10).boxed(), LoafType::Integer(10).boxed())
-                .evaluate(&Vec::<usize>::new()),
+                .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Boolean(false)
         );
     }
@@ -362,7 +1011,7 @@ pub mod state_rules_tests {
     fn lte_rule_false_when_lhs_gt_rhs() {
         assert_eq!(
             LteNode::new(LoafType::Integer(10).boxed(), LoafType::Integer(5).boxed())
-                .evaluate(&Vec::<usize>::new()),
+                .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Boolean(false)
         );
     }
@@ -370,7 +1019,7 @@ pub mod state_rules_tests {
     fn lte_rule_false_when_lhs_lt_rhs() {
         assert_eq!(
             LteNode::new(LoafType::Integer(5).boxed(), LoafType::Integer(10).boxed())
-                .evaluate(&Vec::<usize>::new()),
+                .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Boolean(true)
         );
     }
@@ -378,7 +1027,7 @@ pub mod state_rules_tests {
     fn lte_rule_true_when_lhs_eq_rhs() {
         assert_eq!(
             LteNode::new(LoafType::Integer(10).boxed(), LoafType::Integer(10).boxed())
-                .evaluate(&Vec::<usize>::new()),
+                .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Boolean(true)
         );
     }
@@ -387,7 +1036,7 @@ pub mod state_rules_tests {
     fn eq_rule_true_when_lhs_eq_rhs() {
         assert_eq!(
             EqNode::new(LoafType::Integer(10).boxed(), LoafType::Integer(10).boxed())
-                .evaluate(&Vec::<usize>::new()),
+                .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Boolean(true)
         );
     }
@@ -395,7 +1044,7 @@ pub mod state_rules_tests {
     fn eq_rule_false_when_lhs_neq_rhs() {
         assert_eq!(
             EqNode::new(LoafType::Integer(10).boxed(), LoafType::Integer(11).boxed())
-                .evaluate(&Vec::<usize>::new()),
+                .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Boolean(false)
         );
     }
@@ -404,7 +1053,7 @@ pub mod state_rules_tests {
     fn neq_rule_true_when_lhs_eq_rhs() {
         assert_eq!(
             NeqNode::new(LoafType::Integer(10).boxed(), LoafType::Integer(10).boxed())
-                .evaluate(&Vec::<usize>::new()),
+                .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Boolean(false)
         );
     }
@@ -412,7 +1061,7 @@ pub mod state_rules_tests {
     fn neq_rule_true_when_lhs_neq_rhs() {
         assert_eq!(
             NeqNode::new(LoafType::Integer(10).boxed(), LoafType::Integer(11).boxed())
-                .evaluate(&Vec::<usize>::new()),
+                .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Boolean(true)
         );
     }
@@ -424,7 +1073,7 @@ pub mod state_rules_tests {
                 LoafType::Boolean(true).boxed(),
                 LoafType::Boolean(true).boxed()
             )
-            .evaluate(&Vec::<usize>::new()),
+            .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Boolean(true)
         );
     }
@@ -435,7 +1084,7 @@ pub mod state_rules_tests {
                 LoafType::Boolean(true).boxed(),
                 LoafType::Boolean(false).boxed()
             )
-            .evaluate(&Vec::<usize>::new()),
+            .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Boolean(false)
         );
     }
@@ -446,7 +1095,7 @@ pub mod state_rules_tests {
                 LoafType::Boolean(false).boxed(),
                 LoafType::Boolean(true).boxed()
             )
-            .evaluate(&Vec::<usize>::new()),
+            .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Boolean(false)
         );
     }
@@ -457,7 +1106,7 @@ pub mod state_rules_tests {
                 LoafType::Boolean(false).boxed(),
                 LoafType::Boolean(false).boxed()
             )
-            .evaluate(&Vec::<usize>::new()),
+            .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Boolean(false)
         );
     }
@@ -469,7 +1118,7 @@ pub mod state_rules_tests {
                 LoafType::Boolean(true).boxed(),
                 LoafType::Boolean(true).boxed()
             )
-            .evaluate(&Vec::<usize>::new()),
+            .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Boolean(true)
         );
     }
@@ -480,7 +1129,7 @@ pub mod state_rules_tests {
                 LoafType::Boolean(true).boxed(),
                 LoafType::Boolean(false).boxed()
             )
-            .evaluate(&Vec::<usize>::new()),
+            .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Boolean(true)
         );
     }
@@ -491,7 +1140,7 @@ pub mod state_rules_tests {
                 LoafType::Boolean(false).boxed(),
                 LoafType::Boolean(true).boxed()
             )
-            .evaluate(&Vec::<usize>::new()),
+            .evaluate(&Cell::default(), &Vec::<usize>::new()),
             LoafType::Boolean(true)
         );
     }
@@ -502,25 +1151,203 @@ pub mod state_rules_tests {
                 LoafType::Boolean(false).boxed(),
                 LoafType::Boolean(false).boxed()
             )
-            .evaluate(&Vec::<usize>::new()),
+            .evaluate(&Cell::default(), &Vec::<usize>::new()),
+            LoafType::Boolean(false)
+        );
+    }
+
+    #[test]
+    fn not_rule_inverts() {
+        assert_eq!(
+            NotNode::new(LoafType::Boolean(true).boxed())
+                .evaluate(&Cell::default(), &Vec::<usize>::new()),
+            LoafType::Boolean(false)
+        );
+        assert_eq!(
+            NotNode::new(NotNode::new(LoafType::Boolean(true).boxed()).boxed())
+                .evaluate(&Cell::default(), &Vec::<usize>::new()),
+            LoafType::Boolean(true)
+        );
+    }
+
+    #[test]
+    fn not_rule_negates_census_threshold() {
+        let crowded = || {
+            NotNode::new(
+                GteNode::new(CensusNode::new(1).boxed(), LoafType::Integer(4).boxed()).boxed(),
+            )
+        };
+        assert_eq!(
+            crowded().evaluate(&Cell::default(), &vec![1usize, 1, 1, 0]),
+            LoafType::Boolean(true)
+        );
+        assert_eq!(
+            crowded().evaluate(&Cell::default(), &vec![1usize, 1, 1, 1]),
             LoafType::Boolean(false)
         );
+        assert_eq!(crowded().referenced_states(), vec![1]);
+    }
+
+    #[test]
+    fn min_and_max_rules() {
+        assert_eq!(
+            MinNode::new(LoafType::Integer(3).boxed(), LoafType::Integer(-2).boxed())
+                .evaluate(&Cell::default(), &Vec::<usize>::new()),
+            LoafType::Integer(-2)
+        );
+        assert_eq!(
+            MaxNode::new(LoafType::Integer(3).boxed(), LoafType::Integer(-2).boxed())
+                .evaluate(&Cell::default(), &Vec::<usize>::new()),
+            LoafType::Integer(3)
+        );
+        // max(census(0), census(1)) >= 3
+        let either_crowded = GteNode::new(
+            MaxNode::new(CensusNode::new(0).boxed(), CensusNode::new(1).boxed()).boxed(),
+            LoafType::Integer(3).boxed(),
+        );
+        assert_eq!(
+            either_crowded.evaluate(&Cell::default(), &vec![0usize, 1, 1, 1]),
+            LoafType::Boolean(true)
+        );
+        assert_eq!(
+            either_crowded.evaluate(&Cell::default(), &vec![0usize, 0, 1, 1]),
+            LoafType::Boolean(false)
+        );
+    }
+
+    #[test]
+    fn nested_min_and_max_rules() {
+        // min(max(census(1), 2), 4) clamps the census to 2..=4
+        let clamped = || {
+            MinNode::new(
+                MaxNode::new(CensusNode::new(1).boxed(), LoafType::Integer(2).boxed()).boxed(),
+                LoafType::Integer(4).boxed(),
+            )
+        };
+        for (alive, expected) in [(0, 2), (3, 3), (6, 4)] {
+            assert_eq!(
+                clamped().evaluate(&Cell::default(), &vec![1usize; alive]),
+                LoafType::Integer(expected)
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "TypeMismatch")]
+    fn max_of_boolean_panics() {
+        MaxNode::new(
+            LoafType::Boolean(true).boxed(),
+            LoafType::Integer(1).boxed(),
+        )
+        .evaluate(&Cell::default(), &Vec::<usize>::new());
     }
 
     #[test]
     fn census_op() {
         assert_eq!(
-            CensusNode::new(0usize).evaluate(&vec!(0, 0, 0, 1, 1, 2)),
+            CensusNode::new(0usize).evaluate(&Cell::default(), &vec!(0, 0, 0, 1, 1, 2)),
             LoafType::Integer(3)
         );
         assert_eq!(
-            CensusNode::new(1usize).evaluate(&vec!(0, 0, 0, 1, 1, 2)),
+            CensusNode::new(1usize).evaluate(&Cell::default(), &vec!(0, 0, 0, 1, 1, 2)),
             LoafType::Integer(2)
         );
         assert_eq!(
-            CensusNode::new(2usize).evaluate(&vec!(0, 0, 0, 1, 1, 2)),
+            CensusNode::new(2usize).evaluate(&Cell::default(), &vec!(0, 0, 0, 1, 1, 2)),
+            LoafType::Integer(1)
+        );
+    }
+
+    #[test]
+    fn total_census_op() {
+        assert_eq!(
+            TotalCensusNode.evaluate(&Cell::default(), &vec!(0usize, 0, 0, 1, 1, 2)),
+            LoafType::Integer(6)
+        );
+        assert_eq!(
+            TotalCensusNode.evaluate(&Cell::default(), &Vec::<usize>::new()),
+            LoafType::Integer(0)
+        );
+    }
+
+    #[test]
+    fn weighted_census_op() {
+        let neighborhood = vec![0usize, 0, 1, 1, 1, 2];
+        let weighted = WeightedCensusNode::new(vec![(1, 1), (0, -1)]);
+        assert_eq!(
+            weighted.evaluate(&Cell::default(), &neighborhood),
+            LoafType::Integer(1)
+        );
+        let threshold = GtNode::new(weighted.boxed(), LoafType::Integer(0).boxed());
+        assert_eq!(
+            threshold.evaluate(&Cell::default(), &neighborhood),
+            LoafType::Boolean(true)
+        );
+        assert_eq!(
+            WeightedCensusNode::new(vec![(2, 5)]).evaluate(&Cell::default(), &neighborhood),
+            LoafType::Integer(5)
+        );
+    }
+
+    #[test]
+    fn weighted_census_overflow_is_an_error() {
+        assert_eq!(
+            WeightedCensusNode::new(vec![(1usize, isize::MAX)])
+                .try_evaluate(&Cell::default(), &vec![1usize, 1]),
+            Err(RuleError::Overflow)
+        );
+    }
+
+    #[test]
+    fn weighted_census_sum_overflow_is_an_error() {
+        assert_eq!(
+            WeightedCensusNode::new(vec![(1usize, isize::MAX), (2, 1)])
+                .try_evaluate(&Cell::default(), &vec![1usize, 2]),
+            Err(RuleError::Overflow)
+        );
+    }
+
+    #[test]
+    fn try_evaluate_propagates_errors_to_the_root() {
+        let rule: ASTRoot<usize, Vec<usize>> = ASTRoot::new(
+            GtNode::new(
+                WeightedCensusNode::new(vec![(1, isize::MAX)]).boxed(),
+                LoafType::Integer(0).boxed(),
+            )
+            .boxed(),
+        );
+        assert_eq!(rule.try_evaluate(&Cell::default(), &vec![1]), Ok(true));
+        assert_eq!(
+            rule.try_evaluate(&Cell::default(), &vec![1, 1]),
+            Err(RuleError::Overflow)
+        );
+        let ruleset = Ruleset::new(vec![(0, (rule, 1))]);
+        assert_eq!(
+            ruleset.try_transition(Cell::default(), vec![1, 1]),
+            Err(RuleError::Overflow)
+        );
+        assert_eq!(
+            DivNode::new(LoafType::Integer(1).boxed(), LoafType::Integer(0).boxed())
+                .try_evaluate(&Cell::default(), &Vec::<usize>::new()),
+            Err(RuleError::DivisionByZero)
+        );
+    }
+
+    #[test]
+    fn ring_census_op() {
+        let neighborhood = vec![(1, 0usize), (1, 1), (2, 1), (2, 1), (3, 1), (4, 1)];
+        assert_eq!(
+            RingCensusNode::new(1usize, 2, 3).evaluate(&Cell::default(), &neighborhood),
+            LoafType::Integer(3)
+        );
+        assert_eq!(
+            RingCensusNode::new(1usize, 1, 1).evaluate(&Cell::default(), &neighborhood),
             LoafType::Integer(1)
         );
+        assert_eq!(
+            RingCensusNode::new(0usize, 2, 4).evaluate(&Cell::default(), &neighborhood),
+            LoafType::Integer(0)
+        );
     }
 
     #[test]
@@ -534,7 +1361,7 @@ pub mod state_rules_tests {
                 .boxed(),
                 CensusNode::new(1).boxed()
             )
-            .evaluate(&vec!(0usize, 0usize, 1usize, 1usize)),
+            .evaluate(&Cell::default(), &vec!(0usize, 0usize, 1usize, 1usize)),
             LoafType::Boolean(true)
         );
         assert_eq!(
@@ -546,8 +1373,355 @@ pub mod state_rules_tests {
                 .boxed(),
                 CensusNode::new(1).boxed()
             )
-            .evaluate(&vec!(0usize, 0usize, 1usize, 1usize, 1usize)),
+            .evaluate(
+                &Cell::default(),
+                &vec!(0usize, 0usize, 1usize, 1usize, 1usize)
+            ),
+            LoafType::Boolean(false)
+        );
+    }
+
+    #[test]
+    fn max_and_min_neighbor_op() {
+        let neighborhood = vec![2usize, 0, 3, 1];
+        assert_eq!(
+            MaxNeighborNode.evaluate(&Cell::default(), &neighborhood),
+            LoafType::Integer(3)
+        );
+        assert_eq!(
+            MinNeighborNode.evaluate(&Cell::default(), &neighborhood),
+            LoafType::Integer(0)
+        );
+        assert_eq!(
+            MaxNeighborNode.evaluate(&Cell::default(), &vec![(1, 4usize), (2, 5)]),
+            LoafType::Integer(5)
+        );
+    }
+
+    #[test]
+    fn max_and_min_neighbor_of_empty_neighborhood() {
+        assert_eq!(
+            MaxNeighborNode.evaluate(&Cell::default(), &Vec::<usize>::new()),
+            LoafType::Integer(-1)
+        );
+        assert_eq!(
+            MinNeighborNode.evaluate(&Cell::default(), &Vec::<usize>::new()),
+            LoafType::Integer(-1)
+        );
+    }
+
+    #[test]
+    fn self_op() {
+        assert_eq!(
+            SelfNode.evaluate(&Cell::new(3usize, 0), &Vec::<usize>::new()),
+            LoafType::Integer(3)
+        );
+        let is_zero = EqNode::new(SelfNode.boxed(), LoafType::Integer(0).boxed());
+        assert_eq!(
+            is_zero.evaluate(&Cell::new(0usize, 2), &vec![1usize]),
+            LoafType::Boolean(true)
+        );
+        assert_eq!(
+            is_zero.evaluate(&Cell::new(1usize, 2), &vec![1usize]),
             LoafType::Boolean(false)
         );
     }
+
+    #[test]
+    fn age_op() {
+        assert_eq!(
+            AgeNode.evaluate(&Cell::new(0usize, 4), &Vec::<usize>::new()),
+            LoafType::Integer(4)
+        );
+    }
+
+    #[test]
+    fn check_states_accepts_declared_states() {
+        let rule: ASTRoot<usize, Vec<usize>> = ASTRoot::new(
+            EqNode::new(CensusNode::new(1).boxed(), LoafType::Integer(3).boxed()).boxed(),
+        );
+        let ruleset = Ruleset::new(vec![(0, (rule, 1))]);
+        let declared = vec![0, 1].into_iter().collect();
+        assert_eq!(ruleset.check_states(&declared), Ok(()));
+    }
+
+    #[test]
+    fn check_states_reports_every_unknown_state() {
+        let rule: ASTRoot<usize, Vec<usize>> = ASTRoot::new(
+            OrNode::new(
+                EqNode::new(CensusNode::new(7).boxed(), LoafType::Integer(3).boxed()).boxed(),
+                EqNode::new(CensusNode::new(1).boxed(), LoafType::Integer(3).boxed()).boxed(),
+            )
+            .boxed(),
+        );
+        let other: ASTRoot<usize, Vec<usize>> = ASTRoot::new(LoafType::Boolean(true).boxed());
+        let ruleset = Ruleset::new(vec![(0, (rule, 8)), (9, (other, 0))]);
+        let declared = vec![0, 1].into_iter().collect();
+        assert_eq!(
+            ruleset.check_states(&declared),
+            Err(vec![
+                RuleStateError::UnknownTo { from: 0, to: 8 },
+                RuleStateError::UnknownCensus { from: 0, state: 7 },
+                RuleStateError::UnknownFrom(9),
+            ])
+        );
+    }
+
+    #[test]
+    fn ruleset_evaluates_transitions_in_order() {
+        let crowded: ASTRoot<usize, Vec<usize>> = ASTRoot::new(
+            GtNode::new(CensusNode::new(1).boxed(), LoafType::Integer(3).boxed()).boxed(),
+        );
+        let lively: ASTRoot<usize, Vec<usize>> = ASTRoot::new(
+            GtNode::new(CensusNode::new(1).boxed(), LoafType::Integer(1).boxed()).boxed(),
+        );
+        let ruleset = Ruleset::new(vec![(0, (crowded, 2)), (0, (lively, 1))]);
+        // Both rules hold; the first given wins
+        assert_eq!(ruleset.transition(Cell::default(), vec![1; 4]), Some(2));
+        assert_eq!(ruleset.transition(Cell::default(), vec![1; 2]), Some(1));
+        assert_eq!(ruleset.transition(Cell::default(), vec![1; 1]), None);
+    }
+
+    #[test]
+    fn from_selectors_expands_states_and_wildcards() {
+        let always: ASTRoot<usize, Vec<usize>> = ASTRoot::new(LoafType::Boolean(true).boxed());
+        let lively: ASTRoot<usize, Vec<usize>> = ASTRoot::new(
+            GtNode::new(CensusNode::new(1).boxed(), LoafType::Integer(1).boxed()).boxed(),
+        );
+        let ruleset = Ruleset::from_selectors(
+            vec![
+                (FromSelector::Any, (always, 0)),
+                (FromSelector::States(vec![1, 2]), (lively, 3)),
+            ],
+            &[0, 1, 2],
+        );
+        // The specific rule is tried first, though declared second
+        assert_eq!(
+            ruleset.transition_traced(Cell::new(1, 0), vec![1; 2]),
+            Some((3, 1))
+        );
+        assert_eq!(
+            ruleset.transition_traced(Cell::new(2, 0), vec![]),
+            Some((0, 0))
+        );
+        assert_eq!(ruleset.transition(Cell::new(0, 0), vec![1; 2]), Some(0));
+        assert_eq!(ruleset.states(), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn states_without_transitions_are_kept() {
+        let always: ASTRoot<usize, Vec<usize>> = ASTRoot::new(LoafType::Boolean(true).boxed());
+        let ruleset = Ruleset::new(vec![(0, (always.clone(), 1))]);
+        assert_eq!(ruleset.transition(Cell::new(1, 0), vec![1; 2]), None);
+        assert_eq!(ruleset.transition_traced(Cell::new(1, 0), vec![]), None);
+        assert_eq!(ruleset.try_transition(Cell::new(1, 0), vec![]), Ok(None));
+
+        // Without a wildcard, states that no selector names have no transitions
+        let ruleset = Ruleset::from_selectors(
+            vec![(FromSelector::States(vec![0]), (always, 1))],
+            &[0, 1, 2],
+        );
+        assert_eq!(ruleset.transition(Cell::new(0, 0), vec![]), Some(1));
+        assert_eq!(ruleset.transition(Cell::new(2, 0), vec![]), None);
+    }
+
+    #[test]
+    fn transition_traced_reports_declaration_order() {
+        let always: ASTRoot<usize, Vec<usize>> = ASTRoot::new(LoafType::Boolean(true).boxed());
+        let lively: ASTRoot<usize, Vec<usize>> = ASTRoot::new(
+            GtNode::new(CensusNode::new(1).boxed(), LoafType::Integer(1).boxed()).boxed(),
+        );
+        let ruleset = Ruleset::new(vec![
+            (1, (always.clone(), 0)),
+            (0, (lively, 1)),
+            (0, (always, 2)),
+        ]);
+        assert_eq!(
+            ruleset.transition_traced(Cell::default(), vec![1; 2]),
+            Some((1, 1))
+        );
+        assert_eq!(
+            ruleset.transition_traced(Cell::default(), vec![]),
+            Some((2, 2))
+        );
+        assert_eq!(
+            ruleset.transition_traced(Cell::new(1, 0), vec![]),
+            Some((0, 0))
+        );
+    }
+
+    #[test]
+    fn check_states_covers_every_transition() {
+        let never: ASTRoot<usize, Vec<usize>> = ASTRoot::new(LoafType::Boolean(false).boxed());
+        let ruleset = Ruleset::new(vec![(0, (never.clone(), 1)), (0, (never, 5))]);
+        let declared = vec![0, 1].into_iter().collect();
+        assert_eq!(
+            ruleset.check_states(&declared),
+            Err(vec![RuleStateError::UnknownTo { from: 0, to: 5 }])
+        );
+    }
+
+    #[test]
+    fn ruleset_counts_states_of_cyclic_rules() {
+        let always: ASTRoot<usize, Vec<usize>> = ASTRoot::new(LoafType::Boolean(true).boxed());
+        let ruleset = Ruleset::new(vec![
+            (0, (always.clone(), 1)),
+            (1, (always.clone(), 2)),
+            (2, (always, 0)),
+        ]);
+        assert_eq!(ruleset.states(), vec![0, 1, 2]);
+        assert_eq!(ruleset.num_states(), 3);
+    }
+
+    #[test]
+    fn ruleset_to_dot() {
+        let never: ASTRoot<usize, Vec<usize>> = ASTRoot::new(LoafType::Boolean(false).boxed());
+        let ruleset = Ruleset::new(vec![
+            (0, (never.clone(), 1)),
+            (1, (never.clone(), 0)),
+            (1, (never, 2)),
+        ]);
+        let names = vec![(0, "DEAD".to_string()), (1, "ALIVE".to_string())]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            ruleset.to_dot(&names),
+            concat!(
+                "digraph {\n",
+                "    \"DEAD\";\n",
+                "    \"ALIVE\";\n",
+                "    \"2\";\n",
+                "    \"DEAD\" -> \"ALIVE\" [label=\"1\"];\n",
+                "    \"ALIVE\" -> \"DEAD\" [label=\"1\"];\n",
+                "    \"ALIVE\" -> \"2\" [label=\"2\"];\n",
+                "}\n",
+            )
+        );
+    }
+
+    fn nested_sum(depth: usize) -> ASTRoot<usize, Vec<usize>> {
+        let mut node: Box<dyn ASTNode<usize, Vec<usize>>> = LoafType::Integer(1).boxed();
+        for _ in 1..depth {
+            node = AddNode::new(node, LoafType::Integer(1).boxed()).boxed();
+        }
+        ASTRoot::new(node)
+    }
+
+    #[test]
+    fn validate_accepts_rule_within_limits() {
+        assert_eq!(nested_sum(256).validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_rejects_deep_rule() {
+        assert_eq!(
+            nested_sum(1000).validate(),
+            Err(RuleComplexityError::TooDeep {
+                depth: 257,
+                limit: 256
+            })
+        );
+    }
+
+    #[test]
+    fn validate_rejects_large_rule() {
+        let limits = RuleLimits {
+            max_depth: 100,
+            max_nodes: 10,
+        };
+        // 6 sums and 7 leaves
+        assert_eq!(
+            nested_sum(7).validate_with_limits(limits),
+            Err(RuleComplexityError::TooLarge {
+                nodes: 11,
+                limit: 10
+            })
+        );
+        assert_eq!(nested_sum(5).validate_with_limits(limits), Ok(()));
+    }
+
+    fn life_rules() -> Ruleset<usize, Vec<usize>> {
+        let born: ASTRoot<usize, Vec<usize>> = ASTRoot::new(
+            EqNode::new(CensusNode::new(1).boxed(), LoafType::Integer(3).boxed()).boxed(),
+        );
+        let dies: ASTRoot<usize, Vec<usize>> = ASTRoot::new(
+            OrNode::new(
+                LtNode::new(CensusNode::new(1).boxed(), LoafType::Integer(2).boxed()).boxed(),
+                GtNode::new(CensusNode::new(1).boxed(), LoafType::Integer(3).boxed()).boxed(),
+            )
+            .boxed(),
+        );
+        Ruleset::new(vec![(0, (born, 1)), (1, (dies, 0))])
+    }
+
+    /// Reads only the first listed neighbor
+    #[derive(Clone)]
+    struct FirstNeighborNode;
+    impl ASTNode<usize, Vec<usize>> for FirstNeighborNode {
+        fn evaluate(&self, _cell: &Cell<usize>, neighborhood: &Vec<usize>) -> LoafType {
+            (neighborhood[0] == 1).into()
+        }
+    }
+
+    /// Every offset within `radius` on both axes, apart from the origin
+    fn square(radius: isize) -> Vec<Coordinate2D> {
+        (-radius..=radius)
+            .cartesian_product(-radius..=radius)
+            .filter(|(x, y)| (*x, *y) != (0, 0))
+            .map(|(x, y)| Coordinate2D::new(x, y))
+            .collect()
+    }
+
+    #[test]
+    fn census_rules_are_isotropic() {
+        assert_eq!(
+            life_rules().is_isotropic(&crate::runtime::presets::moore_2d()),
+            Ok(true)
+        );
+        // Far too many configurations to try, but counts can't tell them
+        // apart anyway
+        assert_eq!(life_rules().is_isotropic(&square(3)), Ok(true));
+    }
+
+    #[test]
+    fn rule_reading_one_neighbor_is_not_isotropic() {
+        let never: ASTRoot<usize, Vec<usize>> = ASTRoot::new(LoafType::Boolean(false).boxed());
+        let ruleset = Ruleset::new(vec![
+            (0, (ASTRoot::new(Box::new(FirstNeighborNode)), 1)),
+            (1, (never, 0)),
+        ]);
+        assert_eq!(
+            ruleset.is_isotropic(&crate::runtime::presets::moore_2d()),
+            Ok(false)
+        );
+        assert_eq!(
+            ruleset.is_isotropic(&square(3)),
+            Err(IsotropyError::TooManyConfigurations {
+                configurations: 1 << 48,
+                limit: MAX_ISOTROPY_CONFIGURATIONS
+            })
+        );
+    }
+
+    #[test]
+    fn is_isotropic_reports_rule_errors() {
+        let failing: ASTRoot<usize, Vec<usize>> = ASTRoot::new(
+            AndNode::new(
+                Box::new(FirstNeighborNode),
+                DivNode::new(LoafType::Integer(1).boxed(), LoafType::Integer(0).boxed()).boxed(),
+            )
+            .boxed(),
+        );
+        let ruleset = Ruleset::new(vec![(0, (failing, 1))]);
+        assert_eq!(
+            ruleset.is_isotropic(&crate::runtime::presets::moore_2d()),
+            Err(IsotropyError::Rule(RuleError::DivisionByZero))
+        );
+    }
+
+    #[test]
+    fn asymmetric_offsets_are_not_isotropic() {
+        let offsets = vec![Coordinate2D::new(1, 0), Coordinate2D::new(-1, 0)];
+        assert_eq!(life_rules().is_isotropic(&offsets), Ok(false));
+    }
 }
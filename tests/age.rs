@@ -0,0 +1,32 @@
+use loaf_lang::datatypes::coords::{BoundingBox1D, Coordinate1D};
+use loaf_lang::runtime::environment::naive::FixedGrid;
+use loaf_lang::runtime::environment::Environment;
+use loaf_lang::runtime::state::{ASTRoot, AgeNode, GteNode, LoafType, Ruleset};
+use loaf_lang::runtime::{Runtime, SynchronousRuntime};
+
+const YOUNG: usize = 0;
+const OLD: usize = 1;
+
+#[cfg(test)]
+pub mod age_integration_tests {
+    use super::*;
+
+    #[test]
+    fn cell_transitions_after_three_ticks_in_state() {
+        let mature: ASTRoot<usize, Vec<usize>> =
+            ASTRoot::new(GteNode::new(AgeNode.boxed(), Box::new(LoafType::Integer(3))).boxed());
+        let never: ASTRoot<usize, Vec<usize>> = ASTRoot::new(Box::new(LoafType::Boolean(false)));
+        let rules = Ruleset::new(vec![(YOUNG, (mature, OLD)), (OLD, (never, OLD))]);
+        let env = FixedGrid::new(vec![].into_boxed_slice(), BoundingBox1D::new(0, 0));
+        let mut rt: SynchronousRuntime<_, _, _, Vec<Coordinate1D>> =
+            SynchronousRuntime::new(rules, env);
+        let coord = Coordinate1D::new(0);
+
+        rt.run_ticks(3);
+        assert_eq!(rt.environment().get_state(coord), Some(YOUNG));
+        assert_eq!(rt.environment().get_age(coord), Some(3));
+        rt.run_tick();
+        assert_eq!(rt.environment().get_state(coord), Some(OLD));
+        assert_eq!(rt.environment().get_age(coord), Some(0));
+    }
+}
@@ -1,9 +1,15 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use loaf_lang::datatypes::coords::{BoundingBox2D, Coordinate, Coordinate2D, Dimension};
+use loaf_lang::datatypes::coords::{
+    BoundingBox2D, Coordinate, Coordinate2D, CoordinateBounds, Dimension, Dimensionality,
+};
 use loaf_lang::runtime::environment::naive::FixedGrid;
-use loaf_lang::runtime::environment::Environment;
-use loaf_lang::runtime::neighborhood::{Rule as NeighborhoodRule, Ruleset as NeighborhoodRuleset};
+use loaf_lang::runtime::environment::{Environment, TooManyCells};
+use loaf_lang::runtime::neighborhood::{
+    NeighborGenerator, Rule as NeighborhoodRule, Ruleset as NeighborhoodRuleset,
+};
+use loaf_lang::runtime::output::{CallbackOutput, JsonlWriter};
+use loaf_lang::runtime::presets::{life_like, moore_2d};
 use loaf_lang::runtime::state::{
     ASTRoot, CensusNode, EqNode, GtNode, LoafType, LtNode, OrNode, Ruleset as StateRuleset,
 };
@@ -12,11 +18,7 @@ use loaf_lang::runtime::{Runtime, SynchronousRuntime};
 const DEAD: usize = 0;
 const ALIVE: usize = 1;
 
-pub fn conway_runtime(
-    bounds: BoundingBox2D,
-    initial_states: HashMap<Coordinate2D, usize>,
-) -> SynchronousRuntime<usize, Vec<usize>, FixedGrid<Coordinate2D, BoundingBox2D>, Vec<Coordinate2D>>
-{
+pub fn conway_rules() -> StateRuleset<usize, Vec<usize>> {
     let revive: ASTRoot<usize, Vec<usize>> = ASTRoot::new(
         EqNode::new(
             CensusNode::new(ALIVE).boxed(),
@@ -39,8 +41,27 @@ pub fn conway_runtime(
         )
         .boxed(),
     );
-    let state_rules: StateRuleset<usize, Vec<usize>> =
-        StateRuleset::new(vec![(DEAD, (revive, ALIVE)), (ALIVE, (die, DEAD))]);
+    StateRuleset::new(vec![(DEAD, (revive, ALIVE)), (ALIVE, (die, DEAD))])
+}
+
+/// The eight cells a knight's move away
+#[derive(Debug, Clone)]
+struct KnightMoves;
+impl NeighborGenerator<Coordinate2D> for KnightMoves {
+    fn offsets(&self, _dimensionality: Dimensionality) -> Vec<Coordinate2D> {
+        vec![(1, 2), (2, 1)]
+            .into_iter()
+            .flat_map(|(x, y)| vec![(x, y), (-x, y), (x, -y), (-x, -y)])
+            .map(|(x, y)| Coordinate2D::new(x, y))
+            .collect()
+    }
+}
+
+pub fn conway_runtime(
+    bounds: BoundingBox2D,
+    initial_states: HashMap<Coordinate2D, usize>,
+) -> SynchronousRuntime<usize, Vec<usize>, FixedGrid<Coordinate2D, BoundingBox2D>, Vec<Coordinate2D>>
+{
     let neighborhood_rules: NeighborhoodRuleset<Coordinate2D> = NeighborhoodRuleset::new(vec![
         NeighborhoodRule::undirected_edge(Dimension::All, 1),
         NeighborhoodRule::compound_rule(
@@ -48,29 +69,25 @@ pub fn conway_runtime(
             NeighborhoodRule::undirected_edge(Dimension::Y, 1),
         ),
     ]);
-    let neighborhood: Vec<Coordinate2D> = neighborhood_rules.into_iter().collect();
-
-    let env = FixedGrid::from_hashmap(neighborhood.into_boxed_slice(), initial_states, bounds);
-    let runtime = SynchronousRuntime::new(state_rules, env);
-    runtime
+    let env = FixedGrid::from_ruleset(neighborhood_rules, initial_states, bounds).unwrap();
+    SynchronousRuntime::new(conway_rules(), env)
 }
 
-fn print_snapshot(snapshot: HashMap<Coordinate2D, usize>) {
-    let mut sorted = snapshot.keys().map(|c| *c).collect::<Vec<_>>();
-    sorted.sort_by_key(|c| (-c.y(), c.x()));
-    let mut last_y = sorted.first().unwrap().y();
-    for coord in sorted {
+fn print_snapshot(cells: impl Iterator<Item = (Coordinate2D, usize)>) {
+    let mut cells = cells.peekable();
+    let mut last_y = cells.peek().unwrap().0.y();
+    for (coord, state) in cells {
         if coord.y() != last_y {
-            println!("");
+            println!();
             last_y = coord.y();
         }
-        if snapshot[&coord] == ALIVE {
+        if state == ALIVE {
             print!("[*]");
         } else {
             print!("[ ]");
         }
     }
-    println!("");
+    println!();
 }
 
 #[cfg(test)]
@@ -92,10 +109,10 @@ pub mod conway_integration_tests {
         let before = rt.environment().snapshot();
 
         println!("Tick 0");
-        print_snapshot(rt.environment().snapshot());
+        print_snapshot(rt.iter_row_major());
         assert!(rt.run_tick().is_empty());
         println!("Tick 1");
-        print_snapshot(rt.environment().snapshot());
+        print_snapshot(rt.iter_row_major());
         assert_eq!(rt.environment().snapshot(), before);
     }
 
@@ -113,7 +130,7 @@ pub mod conway_integration_tests {
         let before = rt.environment().snapshot();
 
         println!("Tick 0");
-        print_snapshot(rt.environment().snapshot());
+        print_snapshot(rt.iter_row_major());
         assert_eq!(
             rt.run_tick(),
             vec!(
@@ -127,7 +144,7 @@ pub mod conway_integration_tests {
         );
 
         println!("Tick 1");
-        print_snapshot(rt.environment().snapshot());
+        print_snapshot(rt.iter_row_major());
         assert_eq!(
             rt.run_tick(),
             vec!(
@@ -142,4 +159,606 @@ pub mod conway_integration_tests {
 
         assert_eq!(rt.environment().snapshot(), before);
     }
+
+    #[test]
+    fn test_conway_has_two_states() {
+        let rt = conway_runtime(BoundingBox2D::new((0, 1), (0, 1)), HashMap::new());
+        assert_eq!(rt.num_states(), 2);
+    }
+
+    #[test]
+    fn test_generation_counts_ticks() {
+        let bounds = BoundingBox2D::new((-1, 1), (-1, 1));
+        let mut rt = conway_runtime(bounds, HashMap::new());
+        assert_eq!(rt.generation(), 0);
+        rt.run_tick();
+        assert_eq!(rt.generation(), 1);
+        rt.run_ticks(4);
+        assert_eq!(rt.generation(), 5);
+    }
+
+    #[test]
+    fn test_perturbation_is_evaluated_next_tick() {
+        let bounds = BoundingBox2D::new((-2, 2), (-2, 2));
+        let mut rt = conway_runtime(bounds, HashMap::new());
+        assert!(rt.run_tick().is_empty());
+
+        rt.perturb(
+            vec![
+                (Coordinate2D::new(1, 0), ALIVE),
+                (Coordinate2D::new(0, 0), ALIVE),
+                (Coordinate2D::new(-1, 0), ALIVE),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        assert_eq!(
+            rt.environment().get_state(Coordinate2D::new(0, 0)),
+            Some(ALIVE)
+        );
+        assert_eq!(
+            rt.run_tick(),
+            vec!(
+                (Coordinate2D::new(1, 0), DEAD),
+                (Coordinate2D::new(-1, 0), DEAD),
+                (Coordinate2D::new(0, 1), ALIVE),
+                (Coordinate2D::new(0, -1), ALIVE),
+            )
+            .into_iter()
+            .collect()
+        );
+    }
+
+    #[test]
+    fn test_init_from_fn_checkerboard() {
+        let bounds = BoundingBox2D::new((-2, 2), (-2, 2));
+        let mut rt = conway_runtime(bounds, HashMap::new());
+        rt.init_from_fn(|c| (c.x() + c.y()).rem_euclid(2) as usize)
+            .unwrap();
+        let env = rt.environment();
+        assert_eq!(env.get_state(Coordinate2D::new(0, 0)), Some(DEAD));
+        assert_eq!(env.get_state(Coordinate2D::new(1, 0)), Some(ALIVE));
+        assert_eq!(env.get_state(Coordinate2D::new(-1, 2)), Some(ALIVE));
+        assert_eq!(env.get_state(Coordinate2D::new(-2, -2)), Some(DEAD));
+        assert_eq!(env.snapshot().values().filter(|s| **s == ALIVE).count(), 12);
+    }
+
+    #[test]
+    fn test_init_from_fn_respects_cell_limit() {
+        let bounds = BoundingBox2D::new((-2, 2), (-2, 2));
+        let mut rt = conway_runtime(bounds, HashMap::new()).with_cell_limit(24);
+        assert_eq!(
+            rt.init_from_fn(|_| ALIVE),
+            Err(TooManyCells {
+                cells: 25,
+                limit: 24
+            })
+        );
+        assert!(rt.environment().snapshot().values().all(|s| *s == DEAD));
+    }
+
+    #[test]
+    fn test_frozen_wall_is_preserved() {
+        let bounds = BoundingBox2D::new((-2, 2), (-2, 2));
+        let wall = (-2..=2)
+            .map(|y| Coordinate2D::new(2, y))
+            .collect::<Vec<_>>();
+        let initial_states = wall.iter().map(|c| (*c, ALIVE)).collect();
+        let mut rt = conway_runtime(bounds, initial_states);
+        rt.freeze(wall.iter().copied().collect());
+
+        // The wall would normally lose its ends, but still seeds births
+        let delta = rt.run_tick();
+        assert!(wall.iter().all(|c| !delta.contains_key(c)));
+        assert_eq!(delta.get(&Coordinate2D::new(1, 0)), Some(&ALIVE));
+
+        rt.run_ticks(5);
+        for coord in &wall {
+            assert_eq!(rt.environment().get_state(*coord), Some(ALIVE));
+        }
+    }
+
+    #[test]
+    fn test_blinker_escapes_horizontal_box() {
+        let bounds = BoundingBox2D::new((-2, 2), (-2, 2));
+        let initial_states = vec![
+            (Coordinate2D::new(1, 0), ALIVE),
+            (Coordinate2D::new(0, 0), ALIVE),
+            (Coordinate2D::new(-1, 0), ALIVE),
+        ]
+        .into_iter()
+        .collect();
+        let mut rt = conway_runtime(bounds, initial_states);
+        let region = BoundingBox2D::new((-1, 1), (0, 0));
+        assert!(!rt.escaped(&region));
+        assert_eq!(rt.run_until_escaped(&region, 10), Some(1));
+        assert!(rt.run_until_escaped(&bounds, 10).is_none());
+    }
+
+    #[test]
+    fn test_life_like_b3_s23_matches_conway() {
+        let bounds = BoundingBox2D::new((-2, 2), (-2, 2));
+        let initial_states: HashMap<_, _> = vec![
+            (Coordinate2D::new(1, 0), ALIVE),
+            (Coordinate2D::new(0, 0), ALIVE),
+            (Coordinate2D::new(-1, 0), ALIVE),
+        ]
+        .into_iter()
+        .collect();
+        let mut conway = conway_runtime(bounds, initial_states.clone());
+        let mut life = life_like(&[3], &[2, 3], bounds, initial_states);
+        for _ in 0..4 {
+            assert_eq!(life.run_tick(), conway.run_tick());
+            assert_eq!(
+                life.environment().snapshot(),
+                conway.environment().snapshot()
+            );
+        }
+    }
+
+    #[test]
+    fn test_damage_spreads_from_single_cell() {
+        let bounds = BoundingBox2D::new((-6, 6), (-6, 6));
+        // R-pentomino
+        let initial_states: HashMap<_, _> = vec![
+            (Coordinate2D::new(0, 1), ALIVE),
+            (Coordinate2D::new(1, 1), ALIVE),
+            (Coordinate2D::new(-1, 0), ALIVE),
+            (Coordinate2D::new(0, 0), ALIVE),
+            (Coordinate2D::new(0, -1), ALIVE),
+        ]
+        .into_iter()
+        .collect();
+        let rt = conway_runtime(bounds, initial_states);
+        let before = rt.environment().snapshot();
+        let distances = rt.damage_spread((Coordinate2D::new(1, 0), ALIVE), 5);
+        assert_eq!(distances.len(), 6);
+        assert_eq!(distances[0], 1);
+        assert!(distances[1..].iter().all(|d| *d > 1));
+        assert_eq!(rt.generation(), 0);
+        assert_eq!(rt.environment().snapshot(), before);
+    }
+
+    #[test]
+    fn test_damage_spreads_from_many_cells() {
+        let bounds = BoundingBox2D::new((-4, 4), (-4, 4));
+        let rt = conway_runtime(bounds, HashMap::new());
+        // A block is still life, so the damage neither grows nor heals
+        let block = vec![
+            (Coordinate2D::new(0, 0), ALIVE),
+            (Coordinate2D::new(1, 0), ALIVE),
+            (Coordinate2D::new(0, 1), ALIVE),
+            (Coordinate2D::new(1, 1), ALIVE),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(rt.damage_spread_many(block, 3), vec![4, 4, 4, 4]);
+    }
+
+    #[test]
+    fn test_cloned_runtime_is_independent() {
+        let bounds = BoundingBox2D::new((-2, 2), (-2, 2));
+        let initial_states = vec![
+            (Coordinate2D::new(1, 0), ALIVE),
+            (Coordinate2D::new(0, 0), ALIVE),
+            (Coordinate2D::new(-1, 0), ALIVE),
+        ]
+        .into_iter()
+        .collect();
+        let mut rt = conway_runtime(bounds, initial_states);
+        let mut clone = rt.clone();
+        assert_eq!(clone.run_tick(), rt.run_tick());
+        assert_eq!(clone.environment().snapshot(), rt.environment().snapshot());
+
+        clone.perturb(vec![(Coordinate2D::new(2, 2), ALIVE)].into_iter().collect());
+        assert_eq!(
+            rt.environment().get_state(Coordinate2D::new(2, 2)),
+            Some(DEAD)
+        );
+        clone.run_tick();
+        assert_eq!(rt.generation(), 1);
+        assert_eq!(clone.generation(), 2);
+    }
+
+    #[test]
+    fn test_fill_region_fills_quadrant() {
+        let bounds = BoundingBox2D::new((-2, 2), (-2, 2));
+        let mut rt = conway_runtime(bounds, HashMap::new());
+        rt.fill_region(BoundingBox2D::new((0, 5), (0, 5)), ALIVE);
+        let env = rt.environment();
+        assert_eq!(env.get_state(Coordinate2D::new(0, 0)), Some(ALIVE));
+        assert_eq!(env.get_state(Coordinate2D::new(2, 2)), Some(ALIVE));
+        assert_eq!(env.get_state(Coordinate2D::new(-1, 0)), Some(DEAD));
+        assert_eq!(env.get_state(Coordinate2D::new(3, 3)), None);
+        assert_eq!(env.snapshot().values().filter(|s| **s == ALIVE).count(), 9);
+    }
+
+    #[test]
+    fn test_blinker_jsonl_stream() {
+        let bounds = BoundingBox2D::new((-2, 2), (-2, 2));
+        let initial_states = vec![
+            (Coordinate2D::new(1, 0), ALIVE),
+            (Coordinate2D::new(0, 0), ALIVE),
+            (Coordinate2D::new(-1, 0), ALIVE),
+        ]
+        .into_iter()
+        .collect();
+        let mut rt = conway_runtime(bounds, initial_states);
+        let mut writer = JsonlWriter::new(Vec::new());
+        for _ in 0..2 {
+            let delta = rt.run_tick();
+            writer.write_tick(rt.generation(), &delta).unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(writer.into_inner()).unwrap(),
+            concat!(
+                "{\"tick\":1,\"changes\":[[-1,0,0],[0,-1,1],[0,1,1],[1,0,0]]}\n",
+                "{\"tick\":2,\"changes\":[[-1,0,1],[0,-1,0],[0,1,0],[1,0,1]]}\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_topology_accessors() {
+        let rt = conway_runtime(BoundingBox2D::new((-2, 2), (-1, 1)), HashMap::new());
+        assert_eq!(rt.dimensionality(), Dimensionality::TwoDimensional);
+        let bounds = rt.environment().bounds();
+        assert_eq!(bounds.cell_count(), 15);
+        assert!(bounds.in_bounds(Coordinate2D::new(-2, 1)));
+        assert!(!bounds.in_bounds(Coordinate2D::new(0, 2)));
+    }
+
+    #[test]
+    fn test_blinker_snapshots_every_other_tick() {
+        let bounds = BoundingBox2D::new((-2, 2), (-2, 2));
+        let initial_states = vec![
+            (Coordinate2D::new(1, 0), ALIVE),
+            (Coordinate2D::new(0, 0), ALIVE),
+            (Coordinate2D::new(-1, 0), ALIVE),
+        ]
+        .into_iter()
+        .collect();
+        let mut rt = conway_runtime(bounds, initial_states);
+        let horizontal = rt.environment().snapshot();
+        let snapshots = rt.snapshots_every(2, 3).collect::<Vec<_>>();
+        assert_eq!(snapshots, vec![horizontal.clone(); 3]);
+        assert_eq!(rt.generation(), 4);
+
+        let snapshots = rt.snapshots_every(1, 2).collect::<Vec<_>>();
+        assert_eq!(snapshots[0], horizontal);
+        assert_eq!(snapshots[1][&Coordinate2D::new(0, 1)], ALIVE);
+        assert_eq!(snapshots[1][&Coordinate2D::new(1, 0)], DEAD);
+    }
+
+    #[test]
+    fn test_detects_single_glider() {
+        let bounds = BoundingBox2D::new((-10, 10), (-10, 10));
+        let initial_states = vec![(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]
+            .into_iter()
+            .map(|(x, y)| (Coordinate2D::new(x, y), ALIVE))
+            .collect();
+        let mut rt = conway_runtime(bounds, initial_states);
+        let spaceships = rt.detect_spaceships(&moore_2d(), 8);
+        assert_eq!(spaceships.len(), 1);
+        assert_eq!(spaceships[0].period, 4);
+        assert_eq!(spaceships[0].displacement, Coordinate2D::new(1, 1));
+        assert_eq!(spaceships[0].cells.len(), 5);
+    }
+
+    #[test]
+    fn test_two_placed_gliders() {
+        let glider = vec![(0, 0), (1, 0), (2, 0), (2, 1), (1, 2)]
+            .into_iter()
+            .map(|(x, y)| Coordinate2D::new(x, y))
+            .collect::<Vec<_>>();
+        let bounds = BoundingBox2D::new((0, 30), (0, 30));
+        let mut rt = conway_runtime(bounds, HashMap::new());
+        rt.place_pattern(&glider, Coordinate2D::new(10, 10), ALIVE);
+        rt.place_pattern(&glider, Coordinate2D::new(20, 5), ALIVE);
+
+        let alive = rt
+            .environment()
+            .snapshot()
+            .into_iter()
+            .filter(|(_, s)| *s == ALIVE)
+            .map(|(c, _)| c)
+            .collect::<HashSet<_>>();
+        let expected = [(10, 10), (20, 5)]
+            .iter()
+            .flat_map(|(x, y)| {
+                glider
+                    .iter()
+                    .map(move |offset| Coordinate2D::new(*x, *y) + *offset)
+            })
+            .collect::<HashSet<_>>();
+        assert_eq!(alive, expected);
+        assert_eq!(rt.detect_spaceships(&moore_2d(), 8).len(), 2);
+    }
+
+    #[test]
+    fn test_replayed_glider_matches_simulation() {
+        let bounds = BoundingBox2D::new((-10, 10), (-10, 10));
+        let initial_states = vec![(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)]
+            .into_iter()
+            .map(|(x, y)| (Coordinate2D::new(x, y), ALIVE))
+            .collect();
+        let mut rt = conway_runtime(bounds, initial_states);
+        let mut replay = rt.clone();
+        let deltas = (0..6).map(|_| rt.run_tick()).collect::<Vec<_>>();
+
+        for delta in &deltas {
+            replay.apply_delta(delta);
+        }
+        assert_eq!(replay.generation(), rt.generation());
+        assert_eq!(replay.environment().snapshot(), rt.environment().snapshot());
+        let corner = Coordinate2D::new(-10, -10);
+        assert_eq!(
+            replay.environment().get_age(corner),
+            rt.environment().get_age(corner)
+        );
+    }
+
+    #[test]
+    fn test_blinker_is_not_a_spaceship() {
+        let bounds = BoundingBox2D::new((-2, 2), (-2, 2));
+        let initial_states = vec![(-1, 0), (0, 0), (1, 0)]
+            .into_iter()
+            .map(|(x, y)| (Coordinate2D::new(x, y), ALIVE))
+            .collect();
+        let mut rt = conway_runtime(bounds, initial_states);
+        assert_eq!(rt.detect_spaceships(&moore_2d(), 8), vec![]);
+        // Search stops once every component has recurred
+        assert_eq!(rt.generation(), 2);
+    }
+
+    #[test]
+    fn test_separate_blinkers_are_two_components() {
+        let bounds = BoundingBox2D::new((-5, 5), (-5, 5));
+        let initial_states = vec![(-4, 0), (-3, 0), (-2, 0), (2, 0), (3, 0), (4, 0)]
+            .into_iter()
+            .map(|(x, y)| (Coordinate2D::new(x, y), ALIVE))
+            .collect();
+        let rt = conway_runtime(bounds, initial_states);
+        let components = rt.components(&moore_2d());
+        assert_eq!(components.len(), 2);
+        assert!(components.iter().all(|c| c.len() == 3));
+    }
+
+    #[test]
+    fn test_diagonally_touching_cells_are_one_component() {
+        let bounds = BoundingBox2D::new((-5, 5), (-5, 5));
+        let initial_states = vec![(0, 0), (1, 1), (2, 2), (2, 3)]
+            .into_iter()
+            .map(|(x, y)| (Coordinate2D::new(x, y), ALIVE))
+            .collect();
+        let rt = conway_runtime(bounds, initial_states);
+        assert_eq!(rt.components(&moore_2d()).len(), 1);
+    }
+
+    #[test]
+    fn test_custom_knight_neighborhood() {
+        let neighborhood: NeighborhoodRuleset<Coordinate2D> =
+            NeighborhoodRuleset::new(vec![NeighborhoodRule::custom(KnightMoves)]);
+        let bounds = BoundingBox2D::new((-3, 3), (-3, 3));
+        let env = FixedGrid::from_ruleset(
+            neighborhood,
+            vec![(Coordinate2D::new(0, 0), ALIVE)].into_iter().collect(),
+            bounds,
+        )
+        .unwrap();
+        let spread: ASTRoot<usize, Vec<usize>> = ASTRoot::new(
+            GtNode::new(
+                CensusNode::new(ALIVE).boxed(),
+                Box::new(LoafType::Integer(0)),
+            )
+            .boxed(),
+        );
+        // ALIVE has no transitions, so live cells stay alive
+        let mut rt: SynchronousRuntime<_, _, _, Vec<Coordinate2D>> =
+            SynchronousRuntime::new(StateRuleset::new(vec![(DEAD, (spread, ALIVE))]), env);
+        rt.run_tick();
+        let alive = rt
+            .environment()
+            .snapshot()
+            .into_iter()
+            .filter(|(_, s)| *s == ALIVE)
+            .map(|(c, _)| c)
+            .collect::<HashSet<_>>();
+        let mut expected = KnightMoves
+            .offsets(Dimensionality::TwoDimensional)
+            .into_iter()
+            .collect::<HashSet<_>>();
+        expected.insert(Coordinate2D::new(0, 0));
+        assert_eq!(alive, expected);
+    }
+
+    #[test]
+    fn test_switching_to_von_neumann_mid_run() {
+        let bounds = BoundingBox2D::new((-2, 2), (-2, 2));
+        let initial_states = vec![(-1, 0), (0, 0), (1, 0)]
+            .into_iter()
+            .map(|(x, y)| (Coordinate2D::new(x, y), ALIVE))
+            .collect();
+        let mut rt = conway_runtime(bounds, initial_states);
+        rt.run_tick();
+        rt.run_tick();
+        let von_neumann =
+            NeighborhoodRuleset::new(vec![NeighborhoodRule::undirected_edge(Dimension::All, 1)]);
+        rt.environment_mut().set_neighborhood(von_neumann).unwrap();
+        assert_eq!(
+            rt.environment()
+                .get_neighborhood(Coordinate2D::new(0, 0))
+                .unwrap()
+                .len(),
+            4
+        );
+        // Only the center has two orthogonal neighbors; no cell has three
+        rt.run_tick();
+        let alive = rt
+            .environment()
+            .snapshot()
+            .into_iter()
+            .filter(|(_, s)| *s == ALIVE)
+            .map(|(c, _)| c)
+            .collect::<Vec<_>>();
+        assert_eq!(alive, vec![Coordinate2D::new(0, 0)]);
+    }
+
+    #[test]
+    fn test_invalid_neighborhood_is_rejected() {
+        let mut rt = conway_runtime(BoundingBox2D::new((-2, 2), (-2, 2)), HashMap::new());
+        let rules =
+            NeighborhoodRuleset::new(vec![NeighborhoodRule::directed_edge(Dimension::Z, 1)]);
+        assert!(rt.environment_mut().set_neighborhood(rules).is_err());
+        assert_eq!(
+            rt.environment()
+                .get_neighborhood(Coordinate2D::new(0, 0))
+                .unwrap()
+                .len(),
+            8
+        );
+    }
+
+    #[test]
+    fn test_iter_row_major_order() {
+        let rt = conway_runtime(BoundingBox2D::new((0, 2), (0, 2)), HashMap::new());
+        assert_eq!(
+            rt.iter_row_major()
+                .map(|(c, _)| (c.x(), c.y()))
+                .collect::<Vec<_>>(),
+            vec![
+                (0, 2),
+                (1, 2),
+                (2, 2),
+                (0, 1),
+                (1, 1),
+                (2, 1),
+                (0, 0),
+                (1, 0),
+                (2, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_activity_of_blinker_and_block() {
+        let bounds = BoundingBox2D::new((-3, 3), (-3, 3));
+        let blinker = vec![(-1, 0), (0, 0), (1, 0)]
+            .into_iter()
+            .map(|(x, y)| (Coordinate2D::new(x, y), ALIVE))
+            .collect();
+        let mut rt = conway_runtime(bounds, blinker);
+        assert_eq!(rt.run_with_activity(6), vec![4; 6]);
+
+        let block = vec![(0, 0), (1, 0), (0, 1), (1, 1)]
+            .into_iter()
+            .map(|(x, y)| (Coordinate2D::new(x, y), ALIVE))
+            .collect();
+        let mut rt = conway_runtime(bounds, block);
+        assert_eq!(rt.run_with_activity(6), vec![0; 6]);
+    }
+
+    #[test]
+    fn test_step_single_neighborhoods() {
+        let rules = conway_rules();
+        assert_eq!(rules.step(DEAD, &[ALIVE, ALIVE, ALIVE, DEAD]), Some(ALIVE));
+        assert_eq!(rules.step(DEAD, &[ALIVE, ALIVE, DEAD, DEAD]), None);
+        assert_eq!(rules.step(ALIVE, &[ALIVE, ALIVE, DEAD]), None);
+        assert_eq!(rules.step(ALIVE, &[ALIVE]), Some(DEAD));
+        assert_eq!(rules.step(ALIVE, &[ALIVE; 4]), Some(DEAD));
+    }
+
+    #[test]
+    fn test_blinker_neighbor_count_histogram() {
+        let bounds = BoundingBox2D::new((-2, 2), (-2, 2));
+        let blinker = vec![(-1, 0), (0, 0), (1, 0)]
+            .into_iter()
+            .map(|(x, y)| (Coordinate2D::new(x, y), ALIVE))
+            .collect();
+        let rt = conway_runtime(bounds, blinker);
+        assert_eq!(
+            rt.neighbor_count_histogram(ALIVE),
+            vec![(0, 10), (1, 8), (2, 5), (3, 2)].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn test_blinker_population_series_json() {
+        let bounds = BoundingBox2D::new((-2, 2), (-2, 2));
+        let blinker = vec![(-1, 0), (0, 0), (1, 0)]
+            .into_iter()
+            .map(|(x, y)| (Coordinate2D::new(x, y), ALIVE))
+            .collect();
+        let mut rt = conway_runtime(bounds, blinker);
+        let names = vec![(DEAD, "DEAD".to_string()), (ALIVE, "ALIVE".to_string())]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            rt.population_series_json(2, &names),
+            "[{\"DEAD\":22,\"ALIVE\":3},{\"DEAD\":22,\"ALIVE\":3},{\"DEAD\":22,\"ALIVE\":3}]"
+        );
+    }
+
+    #[test]
+    fn test_center_of_mass_of_block() {
+        let bounds = BoundingBox2D::new((-3, 3), (-3, 3));
+        let block = vec![(0, 0), (1, 0), (0, 1), (1, 1)]
+            .into_iter()
+            .map(|(x, y)| (Coordinate2D::new(x, y), ALIVE))
+            .collect();
+        let rt = conway_runtime(bounds, block);
+        assert_eq!(rt.center_of_mass(ALIVE), Some((0.5, 0.5)));
+    }
+
+    #[test]
+    fn test_center_of_mass_of_single_cell() {
+        let bounds = BoundingBox2D::new((-3, 3), (-3, 3));
+        let mut rt = conway_runtime(bounds, HashMap::new());
+        assert_eq!(rt.center_of_mass(ALIVE), None);
+        rt.perturb(
+            vec![(Coordinate2D::new(2, -1), ALIVE)]
+                .into_iter()
+                .collect(),
+        );
+        assert_eq!(rt.center_of_mass(ALIVE), Some((2.0, -1.0)));
+    }
+
+    #[test]
+    fn test_blinker_rgba_frames() {
+        let bounds = BoundingBox2D::new((-2, 2), (-2, 2));
+        let blinker = vec![(-1, 0), (0, 0), (1, 0)]
+            .into_iter()
+            .map(|(x, y)| (Coordinate2D::new(x, y), ALIVE))
+            .collect();
+        let mut rt = conway_runtime(bounds, blinker);
+        let mut frames = Vec::new();
+        let mut output = CallbackOutput::new(
+            |s| if s == ALIVE { [255; 4] } else { [0, 0, 0, 255] },
+            |tick, width, height, pixels: &[u8]| {
+                assert_eq!((width, height), (5, 5));
+                assert_eq!(pixels.len(), 5 * 5 * 4);
+                // Alive pixels, as (column, row)
+                let alive = (0..25)
+                    .filter(|i| pixels[i * 4] == 255)
+                    .map(|i| (i % 5, i / 5))
+                    .collect::<Vec<_>>();
+                frames.push((tick, alive));
+            },
+        );
+        output
+            .write_frame(rt.generation(), &rt.environment().snapshot())
+            .unwrap();
+        rt.run_tick();
+        output
+            .write_frame(rt.generation(), &rt.environment().snapshot())
+            .unwrap();
+        drop(output);
+        assert_eq!(
+            frames,
+            vec![
+                (0, vec![(1, 2), (2, 2), (3, 2)]),
+                (1, vec![(2, 1), (2, 2), (2, 3)]),
+            ]
+        );
+    }
 }
@@ -0,0 +1,551 @@
+use std::collections::{HashMap, HashSet};
+
+use loaf_lang::datatypes::coords::{
+    BoundingBox1D, BoundingBox2D, Coordinate, Coordinate1D, Coordinate2D, Dimension,
+};
+use loaf_lang::datatypes::neighborhood::Neighborhood;
+use loaf_lang::runtime::batch::SimulationBatch;
+use loaf_lang::runtime::environment::naive::FixedGrid;
+use loaf_lang::runtime::environment::ring::RingGrid;
+use loaf_lang::runtime::environment::Environment;
+use loaf_lang::runtime::neighborhood::{Rule as NeighborhoodRule, Ruleset as NeighborhoodRuleset};
+use loaf_lang::runtime::output::SpaceTimeOutput;
+use loaf_lang::runtime::snapshot::{Snapshot, SnapshotBounds, SnapshotError};
+use loaf_lang::runtime::state::{
+    ASTRoot, AgeNode, CensusNode, GtNode, GteNode, LoafType, RingCensusNode, RuleError,
+    RuleStateError, Ruleset, TotalCensusNode, WeightedCensusNode,
+};
+use loaf_lang::runtime::{Runtime, SynchronousRuntime, UpdateMode};
+
+const OFF: usize = 0;
+const ON: usize = 1;
+
+fn always() -> ASTRoot<usize, Vec<usize>> {
+    ASTRoot::new(Box::new(LoafType::Boolean(true)))
+}
+
+/// Every cell flips between OFF and ON each tick
+pub fn flipping_runtime(
+    bounds: BoundingBox1D,
+) -> SynchronousRuntime<usize, Vec<usize>, FixedGrid<Coordinate1D, BoundingBox1D>, Vec<Coordinate1D>>
+{
+    let rules = Ruleset::new(vec![(OFF, (always(), ON)), (ON, (always(), OFF))]);
+    let env = FixedGrid::new(vec![].into_boxed_slice(), bounds);
+    SynchronousRuntime::new(rules, env)
+}
+
+/// Every neighbor a `CrowdEnvironment` cell sees is in every state at once
+#[derive(Debug, Clone)]
+pub struct Crowd(usize);
+impl Neighborhood<usize> for Crowd {
+    fn count(&self, _state: usize) -> usize {
+        self.0
+    }
+    fn total(&self) -> usize {
+        self.0
+    }
+    fn max_state(&self) -> Option<usize> {
+        None
+    }
+    fn min_state(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// A row of cells each seeing the same crowd of neighbors, implementing
+/// only the methods `Environment` requires
+#[derive(Debug, Clone, Default)]
+pub struct CrowdEnvironment {
+    cells: HashMap<Coordinate1D, usize>,
+    next: HashMap<Coordinate1D, usize>,
+    scheduled: HashSet<Coordinate1D>,
+    crowd: usize,
+}
+impl CrowdEnvironment {
+    pub fn new(len: isize, crowd: usize) -> Self {
+        let cells = (0..len).map(|x| (Coordinate1D::new(x), OFF)).collect();
+        let scheduled = (0..len).map(Coordinate1D::new).collect();
+        Self {
+            cells,
+            scheduled,
+            crowd,
+            ..Self::default()
+        }
+    }
+}
+impl Environment<Coordinate1D, usize, Crowd, Vec<Coordinate1D>> for CrowdEnvironment {
+    fn set_state(&mut self, ident: Coordinate1D, state: usize) {
+        self.next.insert(ident, state);
+    }
+    fn get_state(&self, ident: Coordinate1D) -> Option<usize> {
+        self.cells.get(&ident).copied()
+    }
+    fn get_neighborhood(&self, ident: Coordinate1D) -> Option<Crowd> {
+        self.cells.get(&ident).map(|_| Crowd(self.crowd))
+    }
+    fn schedule(&mut self, ident: Coordinate1D) {
+        self.scheduled.insert(ident);
+    }
+    fn deschedule(&mut self, ident: Coordinate1D) {
+        self.scheduled.remove(&ident);
+    }
+    fn get_schedule(&self) -> Vec<Coordinate1D> {
+        let mut schedule = self.scheduled.iter().copied().collect::<Vec<_>>();
+        schedule.sort();
+        schedule
+    }
+    fn snapshot(&self) -> HashMap<Coordinate1D, usize> {
+        self.cells.clone()
+    }
+    fn tick(&mut self) {
+        self.cells.extend(self.next.drain());
+    }
+}
+
+#[cfg(test)]
+pub mod runtime_integration_tests {
+    use super::*;
+
+    #[test]
+    fn test_equilibrium_of_flipping_rule() {
+        let mut rt = flipping_runtime(BoundingBox1D::new(0, 9));
+        let equilibrium = rt.estimate_equilibrium(100, 5);
+        assert!((equilibrium[&OFF] - 0.5).abs() < 1e-9);
+        assert!((equilibrium[&ON] - 0.5).abs() < 1e-9);
+    }
+
+    fn count_on(cells: &HashMap<Coordinate1D, usize>) -> i64 {
+        cells.values().filter(|s| **s == ON).count() as i64
+    }
+
+    #[test]
+    fn test_flipping_half_the_cells_conserves_on_count() {
+        let mut rt = flipping_runtime(BoundingBox1D::new(0, 9));
+        rt.fill_region(BoundingBox1D::new(0, 4), ON);
+        assert_eq!(rt.check_conservation(count_on, 10), Ok(()));
+    }
+
+    #[test]
+    fn test_flipping_uniform_cells_breaks_conservation() {
+        let mut rt = flipping_runtime(BoundingBox1D::new(0, 9));
+        assert_eq!(rt.check_conservation(count_on, 10), Err((1, 0, 10)));
+    }
+
+    #[test]
+    fn test_explicit_neighborhood_sees_listed_offsets() {
+        let neighborhood: NeighborhoodRuleset<Coordinate1D> = NeighborhoodRuleset::new(vec![
+            NeighborhoodRule::explicit(vec![Coordinate1D::new(-1), Coordinate1D::new(3)]),
+        ]);
+        let mut env = FixedGrid::new(
+            neighborhood
+                .into_iter()
+                .collect::<Vec<_>>()
+                .into_boxed_slice(),
+            BoundingBox1D::new(0, 9),
+        );
+        env.perturb(
+            vec![(Coordinate1D::new(4), 1), (Coordinate1D::new(8), 2)]
+                .into_iter()
+                .collect(),
+        );
+        assert_eq!(env.get_neighborhood(Coordinate1D::new(5)), Some(vec![1, 2]));
+        // Only the -1 offset from 9 lies in bounds
+        assert_eq!(env.get_neighborhood(Coordinate1D::new(9)), Some(vec![2]));
+    }
+
+    #[test]
+    fn test_progress_reported_every_ten_ticks() {
+        let mut rt = flipping_runtime(BoundingBox1D::new(0, 9));
+        let mut reports = Vec::new();
+        rt.run_with_progress(100, 10, |tick, total| reports.push((tick, total)));
+        assert_eq!(reports, (1..=10).map(|i| (i * 10, 100)).collect::<Vec<_>>());
+        assert_eq!(rt.generation(), 100);
+    }
+
+    #[test]
+    fn test_entropy_of_uniform_and_even_grids() {
+        let mut rt = flipping_runtime(BoundingBox1D::new(0, 9));
+        assert_eq!(rt.shannon_entropy(), 0.0);
+        rt.fill_region(BoundingBox1D::new(0, 4), ON);
+        assert!((rt.shannon_entropy() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_set_rules_keeps_cells() {
+        let mut rt = flipping_runtime(BoundingBox1D::new(0, 9));
+        rt.fill_region(BoundingBox1D::new(0, 4), ON);
+        let before = rt.environment().snapshot();
+        let never: ASTRoot<usize, Vec<usize>> = ASTRoot::new(Box::new(LoafType::Boolean(false)));
+        let frozen = Ruleset::new(vec![(OFF, (never.clone(), ON)), (ON, (never, OFF))]);
+        let declared = vec![OFF, ON].into_iter().collect();
+        rt.set_rules(frozen, &declared).unwrap();
+        assert_eq!(rt.environment().snapshot(), before);
+        rt.run_ticks(3);
+        assert_eq!(rt.environment().snapshot(), before);
+    }
+
+    #[test]
+    fn test_set_rules_rejects_undeclared_states() {
+        let mut rt = flipping_runtime(BoundingBox1D::new(0, 9));
+        let rules = Ruleset::new(vec![(OFF, (always(), 7)), (ON, (always(), OFF))]);
+        let declared = vec![OFF, ON].into_iter().collect();
+        assert_eq!(
+            rt.set_rules(rules, &declared).err(),
+            Some(vec![RuleStateError::UnknownTo { from: OFF, to: 7 }])
+        );
+        // The flipping rules are still in place
+        rt.run_tick();
+        assert!(rt.environment().snapshot().values().all(|s| *s == ON));
+    }
+
+    #[test]
+    fn test_batch_runs_each_runtime_independently() {
+        let runtimes = (0..3)
+            .map(|i| {
+                let mut rt = flipping_runtime(BoundingBox1D::new(0, 9));
+                rt.fill_region(BoundingBox1D::new(0, i * 3), ON);
+                rt
+            })
+            .collect::<Vec<_>>();
+        let expected = runtimes
+            .iter()
+            .cloned()
+            .map(|mut rt| {
+                rt.run_ticks(3);
+                rt.environment().snapshot()
+            })
+            .collect::<Vec<_>>();
+        let mut batch = SimulationBatch::new(runtimes);
+        let snapshots = batch.run_all(3);
+        assert_eq!(snapshots, expected);
+        assert_ne!(snapshots[0], snapshots[1]);
+        assert_ne!(snapshots[1], snapshots[2]);
+        assert!(batch.runtimes().iter().all(|rt| rt.generation() == 3));
+    }
+
+    #[test]
+    fn test_disallowed_states_are_clamped_to_default() {
+        let rules = Ruleset::new(vec![(OFF, (always(), ON)), (ON, (always(), 7))]);
+        let env = FixedGrid::new(vec![].into_boxed_slice(), BoundingBox1D::new(0, 4));
+        let mut rt: SynchronousRuntime<_, _, _, Vec<Coordinate1D>> =
+            SynchronousRuntime::new(rules, env)
+                .with_allowed_states(vec![OFF, ON].into_iter().collect());
+        rt.run_tick();
+        assert!(rt.environment().snapshot().values().all(|s| *s == ON));
+        rt.run_tick();
+        assert!(rt.environment().snapshot().values().all(|s| *s == OFF));
+    }
+
+    #[test]
+    fn test_clamped_cells_are_reported() {
+        let rules = Ruleset::new(vec![(OFF, (always(), ON)), (ON, (always(), 7))]);
+        let env = FixedGrid::new(vec![].into_boxed_slice(), BoundingBox1D::new(0, 4));
+        let mut rt: SynchronousRuntime<_, _, _, Vec<Coordinate1D>> =
+            SynchronousRuntime::new(rules, env)
+                .with_allowed_states(vec![OFF, ON].into_iter().collect());
+
+        let (delta, clamped) = rt.try_run_tick_with_clamps().unwrap();
+        assert_eq!(delta.len(), 5);
+        assert!(clamped.is_empty());
+
+        let (delta, mut clamped) = rt.try_run_tick_with_clamps().unwrap();
+        assert!(delta.values().all(|s| *s == OFF));
+        clamped.sort();
+        assert_eq!(clamped, (0..=4).map(Coordinate1D::new).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_space_time_diagram_grows_a_row_per_tick() {
+        let bounds = BoundingBox1D::new(0, 9);
+        let mut rt = flipping_runtime(bounds);
+        let mut output = SpaceTimeOutput::new(bounds, |s| [0, 0, 0, 255 * s as u8], 3).unwrap();
+        output.write_row(&rt.environment().snapshot());
+        for tick in 1..=4 {
+            rt.run_tick();
+            output.write_row(&rt.environment().snapshot());
+            assert_eq!(output.height(), 3 * (tick + 1));
+        }
+        assert_eq!(output.width(), 30);
+        assert_eq!(output.pixels().len(), 30 * 15 * 4);
+        // The second row of cells is ON, so opaque
+        assert_eq!(output.pixels()[30 * 3 * 4 + 3], 255);
+        assert_eq!(output.pixels()[3], 0);
+    }
+
+    #[test]
+    fn test_checkerboard_updates_alternate_sublattices() {
+        let mut synchronous = flipping_runtime(BoundingBox1D::new(0, 9));
+        let mut checkerboard = synchronous
+            .clone()
+            .with_update_mode(UpdateMode::Checkerboard);
+        synchronous.run_tick();
+        assert!(synchronous
+            .environment()
+            .snapshot()
+            .values()
+            .all(|s| *s == ON));
+
+        let delta = checkerboard.run_tick();
+        assert_eq!(delta.len(), 5);
+        let snapshot = checkerboard.environment().snapshot();
+        assert!(snapshot
+            .iter()
+            .all(|(c, s)| *s == if c.x() % 2 == 0 { ON } else { OFF }));
+
+        checkerboard.run_tick();
+        assert_eq!(
+            checkerboard.environment().snapshot(),
+            synchronous.environment().snapshot()
+        );
+        assert_eq!(checkerboard.generation(), 2);
+    }
+
+    #[test]
+    fn test_traced_tick_attributes_rules() {
+        let mut rt = flipping_runtime(BoundingBox1D::new(0, 9));
+        rt.fill_region(BoundingBox1D::new(0, 3), ON);
+        let trace = rt.run_tick_traced();
+        assert_eq!(trace.len(), 10);
+        for (coord, state, rule) in trace {
+            if coord.x() <= 3 {
+                assert_eq!((state, rule), (OFF, 1));
+            } else {
+                assert_eq!((state, rule), (ON, 0));
+            }
+        }
+        assert_eq!(rt.generation(), 1);
+    }
+
+    #[test]
+    fn test_try_run_tick_reports_overflow_without_ticking() {
+        let neighborhood: NeighborhoodRuleset<Coordinate1D> =
+            NeighborhoodRuleset::new(vec![NeighborhoodRule::undirected_edge(Dimension::X, 1)]);
+        let env = FixedGrid::new(
+            neighborhood
+                .into_iter()
+                .collect::<Vec<_>>()
+                .into_boxed_slice(),
+            BoundingBox1D::new(0, 4),
+        );
+        let heavy: ASTRoot<usize, Vec<usize>> = ASTRoot::new(Box::new(GtNode::new(
+            Box::new(WeightedCensusNode::new(vec![(ON, isize::MAX)])),
+            Box::new(LoafType::Integer(0)),
+        )));
+        let rules = Ruleset::new(vec![(OFF, (always(), ON)), (ON, (heavy, OFF))]);
+        let mut rt: SynchronousRuntime<_, _, _, Vec<Coordinate1D>> =
+            SynchronousRuntime::new(rules, env);
+        assert!(rt.try_run_tick().is_ok());
+        let before = rt.environment().snapshot();
+        // Inner cells now have two ON neighbors, overflowing the weighted sum
+        assert_eq!(rt.try_run_tick(), Err(RuleError::Overflow));
+        assert_eq!(rt.environment().snapshot(), before);
+        assert_eq!(rt.generation(), 1);
+    }
+
+    #[test]
+    fn test_run_timed_report() {
+        let mut rt = flipping_runtime(BoundingBox1D::new(0, 9));
+        rt.fill_region(BoundingBox1D::new(0, 2), ON);
+        let report = rt.run_timed(5);
+        assert_eq!(report.ticks, 5);
+        assert!(report.min_tick <= report.max_tick);
+        assert!(report.max_tick <= report.total);
+        assert_eq!(report.live_cells, 7);
+        assert_eq!(rt.generation(), 5);
+
+        let report = rt.run_timed(0);
+        assert_eq!(report.min_tick, report.max_tick);
+        assert_eq!(report.live_cells, 7);
+    }
+
+    #[test]
+    fn test_resumed_run_matches_uninterrupted_run() {
+        // Cells switch on after three ticks and off after two, so the
+        // outcome depends on ages as well as states, and checkerboard ticks
+        // on the generation
+        let aged = |ticks| -> ASTRoot<usize, Vec<usize>> {
+            ASTRoot::new(GteNode::new(AgeNode.boxed(), Box::new(LoafType::Integer(ticks))).boxed())
+        };
+        let rules = Ruleset::new(vec![(OFF, (aged(3), ON)), (ON, (aged(2), OFF))]);
+        let bounds = BoundingBox1D::new(0, 9);
+        let mut uninterrupted: SynchronousRuntime<_, _, _, Vec<Coordinate1D>> =
+            SynchronousRuntime::new(
+                rules.clone(),
+                FixedGrid::new(vec![].into_boxed_slice(), bounds),
+            )
+            .with_update_mode(UpdateMode::Checkerboard);
+        uninterrupted.run_ticks(5);
+
+        let bytes = uninterrupted.to_snapshot().to_bytes();
+        let snapshot = Snapshot::from_bytes(&bytes).unwrap();
+        let mut resumed = SynchronousRuntime::from_snapshot(
+            rules,
+            FixedGrid::new(vec![].into_boxed_slice(), bounds),
+            &snapshot,
+        )
+        .unwrap();
+        assert_eq!(resumed.update_mode(), UpdateMode::Checkerboard);
+        assert_eq!(resumed.to_snapshot(), snapshot);
+        for _ in 0..8 {
+            uninterrupted.run_tick();
+            resumed.run_tick();
+            assert_eq!(resumed.to_snapshot(), uninterrupted.to_snapshot());
+        }
+    }
+
+    #[test]
+    fn test_resume_restores_allowed_states_and_checks_bounds() {
+        let rules = Ruleset::new(vec![(OFF, (always(), ON)), (ON, (always(), 7))]);
+        let env = FixedGrid::new(vec![].into_boxed_slice(), BoundingBox1D::new(0, 4));
+        let rt: SynchronousRuntime<_, _, _, Vec<Coordinate1D>> =
+            SynchronousRuntime::new(rules.clone(), env)
+                .with_allowed_states(vec![OFF, ON].into_iter().collect());
+        let snapshot = rt.to_snapshot();
+        assert_eq!(
+            snapshot.bounds,
+            SnapshotBounds::Box1D(BoundingBox1D::new(0, 4))
+        );
+
+        let mut resumed = SynchronousRuntime::from_snapshot(
+            rules.clone(),
+            FixedGrid::new(vec![].into_boxed_slice(), BoundingBox1D::new(0, 4)),
+            &snapshot,
+        )
+        .unwrap();
+        let (_, clamped) = resumed.try_run_tick_with_clamps().unwrap();
+        assert!(clamped.is_empty());
+        let (_, clamped) = resumed.try_run_tick_with_clamps().unwrap();
+        assert_eq!(clamped.len(), 5);
+
+        assert!(matches!(
+            SynchronousRuntime::from_snapshot(
+                rules,
+                FixedGrid::new(vec![].into_boxed_slice(), BoundingBox1D::new(0, 5)),
+                &snapshot,
+            ),
+            Err(SnapshotError::BoundsMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_ring_census_runs_on_a_ring_grid() {
+        // Cells switch on when a neighbor exactly two cells away is on
+        let at_two: ASTRoot<usize, Vec<(usize, usize)>> = ASTRoot::new(
+            GtNode::new(
+                RingCensusNode::new(ON, 2, 2).boxed(),
+                Box::new(LoafType::Integer(0)),
+            )
+            .boxed(),
+        );
+        let rules = Ruleset::new(vec![(OFF, (at_two, ON))]);
+        let neighborhood =
+            NeighborhoodRuleset::new(vec![NeighborhoodRule::undirected_circle(Dimension::All, 2)]);
+        let grid = FixedGrid::from_ruleset(
+            neighborhood,
+            vec![(Coordinate2D::new(0, 0), ON)].into_iter().collect(),
+            BoundingBox2D::new((-3, 3), (-3, 3)),
+        )
+        .unwrap();
+        let mut rt: SynchronousRuntime<_, _, _, Vec<Coordinate2D>> =
+            SynchronousRuntime::new(rules, RingGrid::new(grid));
+        rt.run_tick();
+
+        let mut on = rt
+            .environment()
+            .snapshot()
+            .into_iter()
+            .filter(|(_, s)| *s == ON)
+            .map(|(c, _)| (c.x(), c.y()))
+            .collect::<Vec<_>>();
+        on.sort();
+        // The diagonals are sqrt(2) away, which rounds up to two
+        assert_eq!(
+            on,
+            vec![
+                (-2, 0),
+                (-1, -1),
+                (-1, 1),
+                (0, -2),
+                (0, 0),
+                (0, 2),
+                (1, -1),
+                (1, 1),
+                (2, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_checkerboard_mode_applies_to_every_driver() {
+        let mut by_tick =
+            flipping_runtime(BoundingBox1D::new(-4, 5)).with_update_mode(UpdateMode::Checkerboard);
+        let mut by_ticks = by_tick.clone();
+        let mut traced = by_tick.clone();
+        let mut timed = by_tick.clone();
+        for _ in 0..3 {
+            by_tick.run_tick();
+        }
+        by_ticks.run_ticks(3);
+        for _ in 0..3 {
+            assert_eq!(traced.run_tick_traced().len(), 5);
+        }
+        timed.run_timed(3);
+
+        // Even generations evaluate the even cells and odd generations the
+        // odd ones, so the even cells have flipped twice and the odd once
+        let expected = (-4..=5)
+            .map(|x| (Coordinate1D::new(x), if x % 2 == 0 { OFF } else { ON }))
+            .collect::<HashMap<_, _>>();
+        for rt in [&by_tick, &by_ticks, &traced, &timed] {
+            assert_eq!(rt.environment().snapshot(), expected);
+            assert_eq!(rt.generation(), 3);
+        }
+    }
+
+    #[test]
+    fn test_environment_default_methods() {
+        let crowded: ASTRoot<usize, Crowd> = ASTRoot::new(
+            GtNode::new(CensusNode::new(ON).boxed(), Box::new(LoafType::Integer(0))).boxed(),
+        );
+        let mut rt: SynchronousRuntime<_, _, _, Vec<Coordinate1D>> = SynchronousRuntime::new(
+            Ruleset::new(vec![(OFF, (crowded, ON))]),
+            CrowdEnvironment::new(3, 1),
+        );
+        let (first, last) = (Coordinate1D::new(0), Coordinate1D::new(2));
+        assert!(rt.environment().contains(first));
+        assert!(!rt.environment().contains(Coordinate1D::new(3)));
+        assert_eq!(rt.environment().get_age(first), Some(0));
+
+        rt.freeze(vec![first].into_iter().collect());
+        rt.perturb(
+            vec![(last, 7), (Coordinate1D::new(3), 7)]
+                .into_iter()
+                .collect(),
+        );
+        assert_eq!(rt.environment().get_state(last), Some(7));
+        assert_eq!(rt.environment().get_state(Coordinate1D::new(3)), None);
+
+        rt.run_tick();
+        let expected = vec![(first, OFF), (Coordinate1D::new(1), ON), (last, 7)];
+        assert_eq!(rt.environment().snapshot(), expected.into_iter().collect());
+    }
+
+    #[test]
+    fn test_try_run_tick_reports_census_overflow() {
+        let crowded: ASTRoot<usize, Crowd> = ASTRoot::new(
+            GtNode::new(CensusNode::new(ON).boxed(), Box::new(LoafType::Integer(0))).boxed(),
+        );
+        let total: ASTRoot<usize, Crowd> = ASTRoot::new(
+            GtNode::new(TotalCensusNode.boxed(), Box::new(LoafType::Integer(0))).boxed(),
+        );
+        for rule in [crowded, total] {
+            let mut rt: SynchronousRuntime<_, _, _, Vec<Coordinate1D>> = SynchronousRuntime::new(
+                Ruleset::new(vec![(OFF, (rule, ON))]),
+                CrowdEnvironment::new(3, usize::MAX),
+            );
+            assert_eq!(rt.try_run_tick(), Err(RuleError::Overflow));
+            assert_eq!(rt.generation(), 0);
+            assert!(rt.environment().snapshot().values().all(|s| *s == OFF));
+        }
+    }
+}